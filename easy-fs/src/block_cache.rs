@@ -0,0 +1,246 @@
+//! In-memory block cache sitting in front of the block device
+
+use super::AsyncBlockDevice;
+use super::BlockDevice;
+use super::BLOCK_SZ;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+use spin::Mutex;
+
+/// Cached copy of a single on-disk block
+pub struct BlockCache {
+    /// cached block data
+    cache: [u8; BLOCK_SZ],
+    /// underlying block id
+    block_id: usize,
+    /// underlying block device
+    block_device: Arc<dyn BlockDevice>,
+    /// whether the cache has been modified since it was loaded
+    modified: bool,
+}
+
+impl BlockCache {
+    /// Load a new BlockCache from disk
+    pub fn new(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Self {
+        let mut cache = [0u8; BLOCK_SZ];
+        block_device.read_block(block_id, &mut cache);
+        Self {
+            cache,
+            block_id,
+            block_device,
+            modified: false,
+        }
+    }
+
+    /// Async counterpart to [`Self::new`]: `.await`s the block device instead of blocking the
+    /// calling hart, so a cache miss here lets the executor poll another task in the meantime.
+    pub async fn new_async(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Self {
+        let mut cache = [0u8; BLOCK_SZ];
+        block_device.read_block_async(block_id, &mut cache).await;
+        Self {
+            cache,
+            block_id,
+            block_device,
+            modified: false,
+        }
+    }
+
+    fn addr_of_offset(&self, offset: usize) -> usize {
+        &self.cache[offset] as *const _ as usize
+    }
+
+    /// Get an immutable reference to a `T` living at `offset` within the cached block
+    pub fn get_ref<T>(&self, offset: usize) -> &T
+    where
+        T: Sized,
+    {
+        let type_size = core::mem::size_of::<T>();
+        assert!(offset + type_size <= BLOCK_SZ);
+        let addr = self.addr_of_offset(offset);
+        unsafe { &*(addr as *const T) }
+    }
+
+    /// Get a mutable reference to a `T` living at `offset` within the cached block
+    pub fn get_mut<T>(&mut self, offset: usize) -> &mut T
+    where
+        T: Sized,
+    {
+        let type_size = core::mem::size_of::<T>();
+        assert!(offset + type_size <= BLOCK_SZ);
+        self.modified = true;
+        let addr = self.addr_of_offset(offset);
+        unsafe { &mut *(addr as *mut T) }
+    }
+
+    /// Read a `T` at `offset` via the closure `f`
+    pub fn read<T, V>(&self, offset: usize, f: impl FnOnce(&T) -> V) -> V {
+        f(self.get_ref(offset))
+    }
+
+    /// Modify a `T` at `offset` via the closure `f`
+    pub fn modify<T, V>(&mut self, offset: usize, f: impl FnOnce(&mut T) -> V) -> V {
+        f(self.get_mut(offset))
+    }
+
+    /// Write the cached block back to the block device if it was modified
+    pub fn sync(&mut self) {
+        if self.modified {
+            self.modified = false;
+            self.block_device.write_block(self.block_id, &self.cache);
+        }
+    }
+
+    /// Async counterpart to [`Self::sync`].
+    pub async fn sync_async(&mut self) {
+        if self.modified {
+            self.modified = false;
+            self.block_device
+                .write_block_async(self.block_id, &self.cache)
+                .await;
+        }
+    }
+}
+
+impl Drop for BlockCache {
+    fn drop(&mut self) {
+        self.sync();
+    }
+}
+
+/// Number of cached blocks kept in memory at once
+const BLOCK_CACHE_SIZE: usize = 16;
+
+/// A simple FIFO-evicted block cache manager
+pub struct BlockCacheManager {
+    queue: VecDeque<(usize, Arc<Mutex<BlockCache>>)>,
+}
+
+impl BlockCacheManager {
+    /// Create an empty manager
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Get the cache for `block_id`, loading it from `block_device` on a miss
+    pub fn get_block_cache(
+        &mut self,
+        block_id: usize,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> Arc<Mutex<BlockCache>> {
+        if let Some(pair) = self.queue.iter().find(|pair| pair.0 == block_id) {
+            Arc::clone(&pair.1)
+        } else {
+            // substitute
+            if self.queue.len() == BLOCK_CACHE_SIZE {
+                // from front to tail
+                if let Some((idx, _)) = self
+                    .queue
+                    .iter()
+                    .enumerate()
+                    .find(|(_, pair)| Arc::strong_count(&pair.1) == 1)
+                {
+                    self.queue.drain(idx..=idx);
+                } else {
+                    panic!("Run out of BlockCache!");
+                }
+            }
+            // load block into mem and push back
+            let block_cache = Arc::new(Mutex::new(BlockCache::new(
+                block_id,
+                Arc::clone(&block_device),
+            )));
+            self.queue.push_back((block_id, Arc::clone(&block_cache)));
+            block_cache
+        }
+    }
+
+    fn find_cached(&self, block_id: usize) -> Option<Arc<Mutex<BlockCache>>> {
+        self.queue
+            .iter()
+            .find(|pair| pair.0 == block_id)
+            .map(|pair| Arc::clone(&pair.1))
+    }
+
+    fn insert(&mut self, block_id: usize, block_cache: Arc<Mutex<BlockCache>>) {
+        if self.queue.len() == BLOCK_CACHE_SIZE {
+            if let Some((idx, _)) = self
+                .queue
+                .iter()
+                .enumerate()
+                .find(|(_, pair)| Arc::strong_count(&pair.1) == 1)
+            {
+                self.queue.drain(idx..=idx);
+            } else {
+                panic!("Run out of BlockCache!");
+            }
+        }
+        self.queue.push_back((block_id, block_cache));
+    }
+}
+
+lazy_static! {
+    /// Shared global instance of the block cache manager
+    pub static ref BLOCK_CACHE_MANAGER: Mutex<BlockCacheManager> =
+        Mutex::new(BlockCacheManager::new());
+}
+
+/// Get the block cache for `block_id` through the global manager
+pub fn get_block_cache(
+    block_id: usize,
+    block_device: Arc<dyn BlockDevice>,
+) -> Arc<Mutex<BlockCache>> {
+    BLOCK_CACHE_MANAGER
+        .lock()
+        .get_block_cache(block_id, block_device)
+}
+
+/// Flush every cached block back to the block device
+pub fn block_cache_sync_all() {
+    let manager = BLOCK_CACHE_MANAGER.lock();
+    for (_, cache) in manager.queue.iter() {
+        cache.lock().sync();
+    }
+}
+
+/// Get the block cache for `block_id` through the global manager, `.await`ing the underlying
+/// device on a miss instead of blocking the hart. The manager's spin lock is only ever held for
+/// the short, synchronous hit-check/insert bookkeeping — never across the `.await` itself, so a
+/// task parked here never holds up anything else that needs the cache manager in the meantime.
+pub async fn get_block_cache_async(
+    block_id: usize,
+    block_device: Arc<dyn BlockDevice>,
+) -> Arc<Mutex<BlockCache>> {
+    if let Some(cached) = BLOCK_CACHE_MANAGER.lock().find_cached(block_id) {
+        return cached;
+    }
+    let loaded = Arc::new(Mutex::new(
+        BlockCache::new_async(block_id, Arc::clone(&block_device)).await,
+    ));
+    let mut manager = BLOCK_CACHE_MANAGER.lock();
+    // another task may have loaded the same block while we were awaiting; prefer theirs so we
+    // don't end up with two `BlockCache`s racing to own the same on-disk block
+    if let Some(cached) = manager.find_cached(block_id) {
+        return cached;
+    }
+    manager.insert(block_id, Arc::clone(&loaded));
+    loaded
+}
+
+/// Async counterpart to [`block_cache_sync_all`]. Collects the cached blocks under the manager
+/// lock, then releases it before `.await`ing each flush so a slow write-back doesn't hold up
+/// every other task that needs the cache manager in the meantime.
+pub async fn block_cache_sync_all_async() {
+    let caches: Vec<Arc<Mutex<BlockCache>>> = BLOCK_CACHE_MANAGER
+        .lock()
+        .queue
+        .iter()
+        .map(|(_, cache)| Arc::clone(cache))
+        .collect();
+    for cache in caches {
+        cache.lock().sync_async().await;
+    }
+}
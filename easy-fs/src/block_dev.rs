@@ -0,0 +1,181 @@
+//! Abstraction of the underlying block device
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+/// A block device backing the filesystem, addressed by fixed-size [`super::BLOCK_SZ`] blocks
+pub trait BlockDevice: Send + Sync + Any {
+    /// Read the block at `block_id` into `buf`
+    fn read_block(&self, block_id: usize, buf: &mut [u8]);
+    /// Write `buf` into the block at `block_id`
+    fn write_block(&self, block_id: usize, buf: &[u8]);
+}
+
+/// Shared state between a pending block I/O request and whatever completes it: a real driver's
+/// interrupt handler calls [`complete`](BlockIoState::complete) once DMA finishes, which wakes
+/// whichever task is parked on the matching [`BlockIoFuture`] — the same pattern
+/// `embassy_time_driver` uses to wake the executor out of `wfi` instead of it polling in a loop.
+struct BlockIoState {
+    done: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl BlockIoState {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            done: AtomicBool::new(false),
+            waker: Mutex::new(None),
+        })
+    }
+
+    /// Called from the device's interrupt handler once the request completes.
+    fn complete(&self) {
+        self.done.store(true, Ordering::Release);
+        if let Some(waker) = self.waker.lock().take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Future returned by [`AsyncBlockDevice::read_block_async`]/
+/// [`AsyncBlockDevice::write_block_async`]; resolves once the
+/// request backing it is marked complete.
+pub struct BlockIoFuture {
+    state: Arc<BlockIoState>,
+}
+
+impl Future for BlockIoFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.state.done.load(Ordering::Acquire) {
+            Poll::Ready(())
+        } else {
+            *self.state.waker.lock() = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// A request queued by [`AsyncBlockDevice`] but not yet run, drained by
+/// [`poll_pending_block_io`] rather than executed inline — the in-tree stand-in for a real
+/// virtio driver's interrupt firing once DMA completes, since no such driver exists in this
+/// tree. `buf`/`len` are a raw pointer into the issuing future's buffer rather than a borrow:
+/// nothing drops that buffer before `run` executes, because the `BlockIoFuture` holding the
+/// matching `state` never reports `Ready` (and every caller in this tree `.await`s it
+/// immediately) until this job has been drained and has called [`BlockIoState::complete`].
+/// `device` is likewise a raw pointer rather than `&'static dyn BlockDevice`/`Arc<dyn
+/// BlockDevice>`, since `read_block_async`/`write_block_async` only have `&self` to work with;
+/// every `BlockDevice` in this kernel lives for the process's whole lifetime (typically behind a
+/// `lazy_static!` or `Arc` held by the filesystem), so this is sound in practice.
+enum PendingBlockIo {
+    Read {
+        device: *const dyn BlockDevice,
+        block_id: usize,
+        buf: *mut u8,
+        len: usize,
+        state: Arc<BlockIoState>,
+    },
+    Write {
+        device: *const dyn BlockDevice,
+        block_id: usize,
+        buf: *const u8,
+        len: usize,
+        state: Arc<BlockIoState>,
+    },
+}
+
+// SAFETY: the raw pointers above only ever point at a `BlockDevice` (`Send + Sync`) and a
+// buffer kept alive until `run` below, per the invariants documented on `PendingBlockIo`.
+unsafe impl Send for PendingBlockIo {}
+
+impl PendingBlockIo {
+    fn run(self) {
+        match self {
+            PendingBlockIo::Read {
+                device,
+                block_id,
+                buf,
+                len,
+                state,
+            } => {
+                let buf = unsafe { core::slice::from_raw_parts_mut(buf, len) };
+                unsafe { &*device }.read_block(block_id, buf);
+                state.complete();
+            }
+            PendingBlockIo::Write {
+                device,
+                block_id,
+                buf,
+                len,
+                state,
+            } => {
+                let buf = unsafe { core::slice::from_raw_parts(buf, len) };
+                unsafe { &*device }.write_block(block_id, buf);
+                state.complete();
+            }
+        }
+    }
+}
+
+lazy_static! {
+    /// Requests issued via [`AsyncBlockDevice`] since the last [`poll_pending_block_io`] drain
+    static ref PENDING_BLOCK_IO: Mutex<VecDeque<PendingBlockIo>> = Mutex::new(VecDeque::new());
+}
+
+/// Run every block I/O request queued since the last drain. Meant to be called once per
+/// scheduler tick, the same way `poll_ready_coroutines` is, so a request issued through
+/// [`AsyncBlockDevice`] actually completes (and wakes its waiting task) on a later tick instead
+/// of before its future is even handed back to the caller.
+pub fn poll_pending_block_io() {
+    let pending: Vec<PendingBlockIo> = PENDING_BLOCK_IO.lock().drain(..).collect();
+    for job in pending {
+        job.run();
+    }
+}
+
+/// Async extension of [`BlockDevice`] for devices whose requests can complete asynchronously:
+/// the default implementation below queues the actual read/write onto [`PENDING_BLOCK_IO`]
+/// instead of performing it inline, so the returned future is genuinely `Pending` until a later
+/// [`poll_pending_block_io`] drain runs it and wakes the waiting task — the same shape a real
+/// interrupt-driven virtio driver would have, with the interrupt replaced by a polled drain
+/// since this tree has no interrupt-driven driver to hook into.
+pub trait AsyncBlockDevice: BlockDevice {
+    /// Issue a read and return a future that resolves once it completes.
+    fn read_block_async(&self, block_id: usize, buf: &mut [u8]) -> BlockIoFuture {
+        let state = BlockIoState::new();
+        let device: *const dyn BlockDevice = self;
+        PENDING_BLOCK_IO.lock().push_back(PendingBlockIo::Read {
+            device,
+            block_id,
+            buf: buf.as_mut_ptr(),
+            len: buf.len(),
+            state: Arc::clone(&state),
+        });
+        BlockIoFuture { state }
+    }
+
+    /// Issue a write and return a future that resolves once it completes.
+    fn write_block_async(&self, block_id: usize, buf: &[u8]) -> BlockIoFuture {
+        let state = BlockIoState::new();
+        let device: *const dyn BlockDevice = self;
+        PENDING_BLOCK_IO.lock().push_back(PendingBlockIo::Write {
+            device,
+            block_id,
+            buf: buf.as_ptr(),
+            len: buf.len(),
+            state: Arc::clone(&state),
+        });
+        BlockIoFuture { state }
+    }
+}
+
+impl<T: BlockDevice + ?Sized> AsyncBlockDevice for T {}
@@ -0,0 +1,720 @@
+//! Layout of a disk block group, i.e. the data structures stored on disk
+
+use super::{get_block_cache, get_block_cache_async, BlockDevice, BLOCK_SZ};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+const EFS_MAGIC: u32 = 0x3b800001;
+/// Number of direct data-block pointers an inode carries inline
+const INODE_DIRECT_COUNT: usize = 27;
+const NAME_LENGTH_LIMIT: usize = 27;
+/// Number of pointers held by a single index block
+const INODE_INDIRECT1_COUNT: usize = BLOCK_SZ / 4;
+const INODE_INDIRECT2_COUNT: usize = INODE_INDIRECT1_COUNT * INODE_INDIRECT1_COUNT;
+const INODE_INDIRECT3_COUNT: usize = INODE_INDIRECT1_COUNT * INODE_INDIRECT2_COUNT;
+
+const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
+const INDIRECT1_BOUND: usize = DIRECT_BOUND + INODE_INDIRECT1_COUNT;
+const INDIRECT2_BOUND: usize = INDIRECT1_BOUND + INODE_INDIRECT2_COUNT;
+const INDIRECT3_BOUND: usize = INDIRECT2_BOUND + INODE_INDIRECT3_COUNT;
+
+/// The filesystem's super block, stored in block 0
+#[repr(C)]
+pub struct SuperBlock {
+    magic: u32,
+    pub total_blocks: u32,
+    pub inode_bitmap_blocks: u32,
+    pub inode_area_blocks: u32,
+    pub data_bitmap_blocks: u32,
+    pub data_area_blocks: u32,
+}
+
+impl SuperBlock {
+    /// Initialize a super block
+    pub fn initialize(
+        &mut self,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+        inode_area_blocks: u32,
+        data_bitmap_blocks: u32,
+        data_area_blocks: u32,
+    ) {
+        *self = Self {
+            magic: EFS_MAGIC,
+            total_blocks,
+            inode_bitmap_blocks,
+            inode_area_blocks,
+            data_bitmap_blocks,
+            data_area_blocks,
+        }
+    }
+
+    /// Check whether the on-disk magic matches
+    pub fn is_valid(&self) -> bool {
+        self.magic == EFS_MAGIC
+    }
+}
+
+/// The type an inode on disk refers to
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum DiskInodeType {
+    /// a regular file
+    File,
+    /// a directory
+    Directory,
+    /// a symbolic link; its data holds the (possibly relative) target path as UTF-8 bytes
+    SymLink,
+}
+
+/// A block's worth of `u32` index pointers
+type IndirectBlock = [u32; BLOCK_SZ / 4];
+/// A raw data block
+type DataBlock = [u8; BLOCK_SZ];
+
+/// On-disk inode: direct pointers plus a direct -> indirect -> double-indirect ->
+/// triple-indirect pointer chain, so file size is bounded only by the triple-indirect
+/// tree's reach instead of a single indirect block.
+#[derive(Clone)]
+#[repr(C)]
+pub struct DiskInode {
+    /// size of the file content in bytes
+    pub size: u32,
+    /// direct data block pointers
+    pub direct: [u32; INODE_DIRECT_COUNT],
+    /// single-indirect pointer: one index block of direct data-block pointers
+    pub indirect1: u32,
+    /// double-indirect pointer: one index block of `indirect1`-style blocks
+    pub indirect2: u32,
+    /// triple-indirect pointer: one index block of `indirect2`-style blocks
+    pub indirect3: u32,
+    /// the inode's type: file, directory or symlink
+    type_: DiskInodeType,
+    /// `S_IRWXU`/`S_IRWXG`/`S_IRWXO` permission bits
+    pub mode: u16,
+    /// owning user id
+    pub uid: u32,
+    /// owning group id
+    pub gid: u32,
+    /// number of hard links pointing at this inode
+    pub nlink: u32,
+}
+
+/// owner can read
+pub const S_IRUSR: u16 = 0o400;
+/// owner can write
+pub const S_IWUSR: u16 = 0o200;
+/// owner can execute/search
+pub const S_IXUSR: u16 = 0o100;
+/// group can read
+pub const S_IRGRP: u16 = 0o040;
+/// group can write
+pub const S_IWGRP: u16 = 0o020;
+/// group can execute/search
+pub const S_IXGRP: u16 = 0o010;
+/// others can read
+pub const S_IROTH: u16 = 0o004;
+/// others can write
+pub const S_IWOTH: u16 = 0o002;
+/// others can execute/search
+pub const S_IXOTH: u16 = 0o001;
+
+/// requested-access bits for [`DiskInode::check_access`], matching POSIX `access(2)`
+pub const R_OK: u8 = 0o4;
+/// write access requested
+pub const W_OK: u8 = 0o2;
+/// execute/search access requested
+pub const X_OK: u8 = 0o1;
+
+impl DiskInode {
+    /// Initialize a disk inode as a file, a directory or a symlink, zeroing its block pointers
+    /// and setting its owner and permission bits.
+    pub fn initialize(&mut self, type_: DiskInodeType, mode: u16, uid: u32, gid: u32) {
+        self.size = 0;
+        self.direct.iter_mut().for_each(|v| *v = 0);
+        self.indirect1 = 0;
+        self.indirect2 = 0;
+        self.indirect3 = 0;
+        self.type_ = type_;
+        self.mode = mode;
+        self.uid = uid;
+        self.gid = gid;
+        self.nlink = 1;
+    }
+
+    /// Check `requested` (some combination of [`R_OK`]/[`W_OK`]/[`X_OK`]) against this inode's
+    /// mode bits, resolved in the usual owner -> group -> other order. The superuser (`uid ==
+    /// 0`) always passes.
+    pub fn check_access(&self, uid: u32, gid: u32, requested: u8) -> bool {
+        if uid == 0 {
+            return true;
+        }
+        let shift = if uid == self.uid {
+            6
+        } else if gid == self.gid {
+            3
+        } else {
+            0
+        };
+        let granted = ((self.mode >> shift) & 0o7) as u8;
+        granted & requested == requested
+    }
+
+    /// whether this inode is a directory
+    pub fn is_dir(&self) -> bool {
+        self.type_ == DiskInodeType::Directory
+    }
+    /// whether this inode is a regular file
+    pub fn is_file(&self) -> bool {
+        self.type_ == DiskInodeType::File
+    }
+    /// whether this inode is a symbolic link
+    pub fn is_symlink(&self) -> bool {
+        self.type_ == DiskInodeType::SymLink
+    }
+
+    /// Resolve the `inner_id`-th block of the file to a physical block id, routing through
+    /// direct -> indirect1 -> indirect2 -> indirect3 ranges as `inner_id` grows.
+    pub fn get_block_id(&self, inner_id: u32, block_device: &Arc<dyn BlockDevice>) -> u32 {
+        let inner_id = inner_id as usize;
+        if inner_id < DIRECT_BOUND {
+            self.direct[inner_id]
+        } else if inner_id < INDIRECT1_BOUND {
+            Self::read_index_block(self.indirect1, inner_id - DIRECT_BOUND, block_device)
+        } else if inner_id < INDIRECT2_BOUND {
+            Self::walk_index_tree(self.indirect2, inner_id - INDIRECT1_BOUND, 1, block_device)
+        } else {
+            assert!(inner_id < INDIRECT3_BOUND, "file offset out of range");
+            Self::walk_index_tree(self.indirect3, inner_id - INDIRECT2_BOUND, 2, block_device)
+        }
+    }
+
+    /// Read the pointer stored at `index` inside the index block `block_id`
+    fn read_index_block(block_id: u32, index: usize, block_device: &Arc<dyn BlockDevice>) -> u32 {
+        get_block_cache(block_id as usize, Arc::clone(block_device))
+            .lock()
+            .read(0, |b: &IndirectBlock| b[index])
+    }
+
+    /// Descend `levels` extra layers of indirection below `root` to resolve `inner_id`
+    /// (already offset relative to `root`) to a data block id.
+    fn walk_index_tree(
+        root: u32,
+        inner_id: usize,
+        levels: usize,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> u32 {
+        let mut block_id = root;
+        let mut inner_id = inner_id;
+        for level in (0..=levels).rev() {
+            let span = INODE_INDIRECT1_COUNT.pow(level as u32);
+            let index = inner_id / span;
+            inner_id %= span;
+            block_id = Self::read_index_block(block_id, index, block_device);
+        }
+        block_id
+    }
+
+    fn _data_blocks(size: u32) -> u32 {
+        (size as usize + BLOCK_SZ - 1) as u32 / BLOCK_SZ as u32
+    }
+
+    /// number of data blocks currently allocated for this inode's size
+    pub fn data_blocks(&self) -> u32 {
+        Self::_data_blocks(self.size)
+    }
+
+    /// Number of index blocks needed beneath an index tree rooted `levels` deep
+    /// (1 for indirect2, 2 for indirect3) to cover `remaining` trailing data blocks.
+    fn index_overhead(remaining: usize, levels: usize) -> usize {
+        let mut overhead = 1; // the root index block itself
+        for level in 1..=levels {
+            let span = INODE_INDIRECT1_COUNT.pow(level as u32);
+            overhead += (remaining + span - 1) / span;
+        }
+        overhead
+    }
+
+    /// Total number of blocks (data blocks plus every index block at every level)
+    /// needed to hold a file of `size` bytes.
+    pub fn total_blocks(size: u32) -> u32 {
+        let data_blocks = Self::_data_blocks(size) as usize;
+        let mut total = data_blocks;
+        if data_blocks > DIRECT_BOUND {
+            total += 1; // indirect1 index block
+        }
+        if data_blocks > INDIRECT1_BOUND {
+            let remaining = (data_blocks - INDIRECT1_BOUND).min(INODE_INDIRECT2_COUNT);
+            total += Self::index_overhead(remaining, 1);
+        }
+        if data_blocks > INDIRECT2_BOUND {
+            let remaining = data_blocks - INDIRECT2_BOUND;
+            total += Self::index_overhead(remaining, 2);
+        }
+        total as u32
+    }
+
+    /// Number of additional blocks needed to grow this inode up to `new_size`
+    pub fn blocks_num_needed(&self, new_size: u32) -> u32 {
+        assert!(new_size >= self.size);
+        Self::total_blocks(new_size) - Self::total_blocks(self.size)
+    }
+
+    /// Grow the inode to `new_size`, consuming `new_blocks` (as allocated by the caller via
+    /// `blocks_num_needed`) to lazily fill in data blocks and, where a range is entered for
+    /// the first time, the index blocks above them.
+    pub fn increase_size(
+        &mut self,
+        new_size: u32,
+        new_blocks: Vec<u32>,
+        block_device: &Arc<dyn BlockDevice>,
+    ) {
+        let mut current_blocks = self.data_blocks();
+        self.size = new_size;
+        let mut total_blocks = self.data_blocks();
+        let mut new_blocks = new_blocks.into_iter();
+        // fill direct
+        while current_blocks < total_blocks.min(DIRECT_BOUND as u32) {
+            self.direct[current_blocks as usize] = new_blocks.next().unwrap();
+            current_blocks += 1;
+        }
+        // alloc indirect1
+        if total_blocks > DIRECT_BOUND as u32 {
+            if current_blocks == DIRECT_BOUND as u32 {
+                self.indirect1 = new_blocks.next().unwrap();
+            }
+            current_blocks -= DIRECT_BOUND as u32;
+            total_blocks -= DIRECT_BOUND as u32;
+        } else {
+            return;
+        }
+        // fill indirect1
+        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect1: &mut IndirectBlock| {
+                while current_blocks < total_blocks.min(INODE_INDIRECT1_COUNT as u32) {
+                    indirect1[current_blocks as usize] = new_blocks.next().unwrap();
+                    current_blocks += 1;
+                }
+            });
+        // alloc indirect2
+        if total_blocks > INODE_INDIRECT1_COUNT as u32 {
+            if current_blocks == INODE_INDIRECT1_COUNT as u32 {
+                self.indirect2 = new_blocks.next().unwrap();
+            }
+            current_blocks -= INODE_INDIRECT1_COUNT as u32;
+            total_blocks -= INODE_INDIRECT1_COUNT as u32;
+        } else {
+            return;
+        }
+        // fill indirect2 from (a0, b0) to (a1, b1)
+        let mut a0 = current_blocks as usize / INODE_INDIRECT1_COUNT;
+        let mut b0 = current_blocks as usize % INODE_INDIRECT1_COUNT;
+        let end2 = total_blocks.min(INODE_INDIRECT2_COUNT as u32) as usize;
+        let a1 = end2 / INODE_INDIRECT1_COUNT;
+        let b1 = end2 % INODE_INDIRECT1_COUNT;
+        get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect2: &mut IndirectBlock| {
+                while (a0 < a1) || (a0 == a1 && b0 < b1) {
+                    if b0 == 0 {
+                        indirect2[a0] = new_blocks.next().unwrap();
+                    }
+                    get_block_cache(indirect2[a0] as usize, Arc::clone(block_device))
+                        .lock()
+                        .modify(0, |indirect1: &mut IndirectBlock| {
+                            indirect1[b0] = new_blocks.next().unwrap();
+                        });
+                    b0 += 1;
+                    if b0 == INODE_INDIRECT1_COUNT {
+                        b0 = 0;
+                        a0 += 1;
+                    }
+                }
+            });
+        current_blocks = current_blocks.max(end2 as u32);
+        // alloc indirect3
+        if total_blocks > INODE_INDIRECT2_COUNT as u32 {
+            if current_blocks == INODE_INDIRECT2_COUNT as u32 {
+                self.indirect3 = new_blocks.next().unwrap();
+            }
+            current_blocks -= INODE_INDIRECT2_COUNT as u32;
+            total_blocks -= INODE_INDIRECT2_COUNT as u32;
+        } else {
+            return;
+        }
+        // fill indirect3 from (a0, b0, c0) to (a1, b1, c1)
+        let mut a0 = current_blocks as usize / INODE_INDIRECT2_COUNT;
+        let rem0 = current_blocks as usize % INODE_INDIRECT2_COUNT;
+        let mut b0 = rem0 / INODE_INDIRECT1_COUNT;
+        let mut c0 = rem0 % INODE_INDIRECT1_COUNT;
+        let a1 = total_blocks as usize / INODE_INDIRECT2_COUNT;
+        let rem1 = total_blocks as usize % INODE_INDIRECT2_COUNT;
+        let b1 = rem1 / INODE_INDIRECT1_COUNT;
+        let c1 = rem1 % INODE_INDIRECT1_COUNT;
+        get_block_cache(self.indirect3 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect3: &mut IndirectBlock| {
+                while (a0 < a1) || (a0 == a1 && (b0 < b1 || (b0 == b1 && c0 < c1))) {
+                    if b0 == 0 && c0 == 0 {
+                        indirect3[a0] = new_blocks.next().unwrap();
+                    }
+                    get_block_cache(indirect3[a0] as usize, Arc::clone(block_device))
+                        .lock()
+                        .modify(0, |indirect2: &mut IndirectBlock| {
+                            if c0 == 0 {
+                                indirect2[b0] = new_blocks.next().unwrap();
+                            }
+                            get_block_cache(indirect2[b0] as usize, Arc::clone(block_device))
+                                .lock()
+                                .modify(0, |indirect1: &mut IndirectBlock| {
+                                    indirect1[c0] = new_blocks.next().unwrap();
+                                });
+                        });
+                    c0 += 1;
+                    if c0 == INODE_INDIRECT1_COUNT {
+                        c0 = 0;
+                        b0 += 1;
+                        if b0 == INODE_INDIRECT1_COUNT {
+                            b0 = 0;
+                            a0 += 1;
+                        }
+                    }
+                }
+            });
+    }
+
+    /// Clear the whole file, walking the whole block tree and returning every block id in it
+    /// (data blocks and the index blocks above them; each index block is listed before the
+    /// blocks it points to) for the caller to `dealloc_data`. Every pointer is read here,
+    /// before the caller frees any block.
+    pub fn clear_size(&mut self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        let mut v: Vec<u32> = Vec::new();
+        let mut data_blocks = self.data_blocks() as usize;
+        self.size = 0;
+        let mut current_blocks = 0usize;
+        // direct
+        while current_blocks < data_blocks.min(DIRECT_BOUND) {
+            v.push(self.direct[current_blocks]);
+            self.direct[current_blocks] = 0;
+            current_blocks += 1;
+        }
+        // indirect1 block
+        if data_blocks > DIRECT_BOUND {
+            v.push(self.indirect1);
+            data_blocks -= DIRECT_BOUND;
+            current_blocks = 0;
+        } else {
+            return v;
+        }
+        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect1: &mut IndirectBlock| {
+                while current_blocks < data_blocks.min(INODE_INDIRECT1_COUNT) {
+                    v.push(indirect1[current_blocks]);
+                    current_blocks += 1;
+                }
+            });
+        self.indirect1 = 0;
+        // indirect2 block
+        if data_blocks > INODE_INDIRECT1_COUNT {
+            v.push(self.indirect2);
+            data_blocks -= INODE_INDIRECT1_COUNT;
+        } else {
+            return v;
+        }
+        let in_indirect2 = data_blocks.min(INODE_INDIRECT2_COUNT);
+        let a1 = in_indirect2 / INODE_INDIRECT1_COUNT;
+        let b1 = in_indirect2 % INODE_INDIRECT1_COUNT;
+        get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect2: &mut IndirectBlock| {
+                for entry in indirect2.iter().take(a1) {
+                    v.push(*entry);
+                    get_block_cache(*entry as usize, Arc::clone(block_device))
+                        .lock()
+                        .modify(0, |indirect1: &mut IndirectBlock| {
+                            for entry in indirect1.iter() {
+                                v.push(*entry);
+                            }
+                        });
+                }
+                if b1 > 0 {
+                    v.push(indirect2[a1]);
+                    get_block_cache(indirect2[a1] as usize, Arc::clone(block_device))
+                        .lock()
+                        .modify(0, |indirect1: &mut IndirectBlock| {
+                            for entry in indirect1.iter().take(b1) {
+                                v.push(*entry);
+                            }
+                        });
+                }
+            });
+        self.indirect2 = 0;
+        // indirect3 block
+        if data_blocks > INODE_INDIRECT2_COUNT {
+            v.push(self.indirect3);
+            data_blocks -= INODE_INDIRECT2_COUNT;
+        } else {
+            return v;
+        }
+        assert!(data_blocks <= INODE_INDIRECT3_COUNT);
+        let a1 = data_blocks / INODE_INDIRECT2_COUNT;
+        let rem1 = data_blocks % INODE_INDIRECT2_COUNT;
+        get_block_cache(self.indirect3 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect3: &mut IndirectBlock| {
+                for entry in indirect3.iter().take(a1) {
+                    v.push(*entry);
+                    get_block_cache(*entry as usize, Arc::clone(block_device))
+                        .lock()
+                        .modify(0, |indirect2: &mut IndirectBlock| {
+                            for entry in indirect2.iter() {
+                                v.push(*entry);
+                                get_block_cache(*entry as usize, Arc::clone(block_device))
+                                    .lock()
+                                    .modify(0, |indirect1: &mut IndirectBlock| {
+                                        for entry in indirect1.iter() {
+                                            v.push(*entry);
+                                        }
+                                    });
+                            }
+                        });
+                }
+                if rem1 > 0 {
+                    let b1 = rem1 / INODE_INDIRECT1_COUNT;
+                    let c1 = rem1 % INODE_INDIRECT1_COUNT;
+                    v.push(indirect3[a1]);
+                    get_block_cache(indirect3[a1] as usize, Arc::clone(block_device))
+                        .lock()
+                        .modify(0, |indirect2: &mut IndirectBlock| {
+                            for entry in indirect2.iter().take(b1) {
+                                v.push(*entry);
+                                get_block_cache(*entry as usize, Arc::clone(block_device))
+                                    .lock()
+                                    .modify(0, |indirect1: &mut IndirectBlock| {
+                                        for entry in indirect1.iter() {
+                                            v.push(*entry);
+                                        }
+                                    });
+                            }
+                            if c1 > 0 {
+                                v.push(indirect2[b1]);
+                                get_block_cache(indirect2[b1] as usize, Arc::clone(block_device))
+                                    .lock()
+                                    .modify(0, |indirect1: &mut IndirectBlock| {
+                                        for entry in indirect1.iter().take(c1) {
+                                            v.push(*entry);
+                                        }
+                                    });
+                            }
+                        });
+                }
+            });
+        self.indirect3 = 0;
+        v
+    }
+
+    /// Read `buf.len()` bytes starting at `offset` into `buf`, returning the number of bytes
+    /// actually read (clamped to the file's size).
+    pub fn read_at(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        let mut start = offset;
+        let end = (offset + buf.len()).min(self.size as usize);
+        if start >= end {
+            return 0;
+        }
+        let mut start_block = start / BLOCK_SZ;
+        let mut read_size = 0usize;
+        loop {
+            let mut end_current_block = (start / BLOCK_SZ + 1) * BLOCK_SZ;
+            end_current_block = end_current_block.min(end);
+            let block_read_size = end_current_block - start;
+            let dst = &mut buf[read_size..read_size + block_read_size];
+            get_block_cache(
+                self.get_block_id(start_block as u32, block_device) as usize,
+                Arc::clone(block_device),
+            )
+            .lock()
+            .read(0, |data_block: &DataBlock| {
+                let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
+                dst.copy_from_slice(src);
+            });
+            read_size += block_read_size;
+            if end_current_block == end {
+                break;
+            }
+            start_block += 1;
+            start = end_current_block;
+        }
+        read_size
+    }
+
+    /// Write `buf` at `offset`. The caller must have already grown the inode (via
+    /// `increase_size`) so every touched block is allocated.
+    pub fn write_at(
+        &mut self,
+        offset: usize,
+        buf: &[u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        let mut start = offset;
+        let end = (offset + buf.len()).min(self.size as usize);
+        assert!(start <= end);
+        let mut start_block = start / BLOCK_SZ;
+        let mut write_size = 0usize;
+        loop {
+            let mut end_current_block = (start / BLOCK_SZ + 1) * BLOCK_SZ;
+            end_current_block = end_current_block.min(end);
+            let block_write_size = end_current_block - start;
+            get_block_cache(
+                self.get_block_id(start_block as u32, block_device) as usize,
+                Arc::clone(block_device),
+            )
+            .lock()
+            .modify(0, |data_block: &mut DataBlock| {
+                let src = &buf[write_size..write_size + block_write_size];
+                let dst = &mut data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_write_size];
+                dst.copy_from_slice(src);
+            });
+            write_size += block_write_size;
+            if end_current_block == end {
+                break;
+            }
+            start_block += 1;
+            start = end_current_block;
+        }
+        write_size
+    }
+
+    /// Async counterpart to [`Self::read_at`]: `.await`s each block fetch instead of blocking
+    /// the calling hart, so the executor can poll another task while this one waits on I/O.
+    pub async fn read_at_async(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        let mut start = offset;
+        let end = (offset + buf.len()).min(self.size as usize);
+        if start >= end {
+            return 0;
+        }
+        let mut start_block = start / BLOCK_SZ;
+        let mut read_size = 0usize;
+        loop {
+            let mut end_current_block = (start / BLOCK_SZ + 1) * BLOCK_SZ;
+            end_current_block = end_current_block.min(end);
+            let block_read_size = end_current_block - start;
+            let dst = &mut buf[read_size..read_size + block_read_size];
+            get_block_cache_async(
+                self.get_block_id(start_block as u32, block_device) as usize,
+                Arc::clone(block_device),
+            )
+            .await
+            .lock()
+            .read(0, |data_block: &DataBlock| {
+                let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
+                dst.copy_from_slice(src);
+            });
+            read_size += block_read_size;
+            if end_current_block == end {
+                break;
+            }
+            start_block += 1;
+            start = end_current_block;
+        }
+        read_size
+    }
+
+    /// Async counterpart to [`Self::write_at`].
+    pub async fn write_at_async(
+        &mut self,
+        offset: usize,
+        buf: &[u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        let mut start = offset;
+        let end = (offset + buf.len()).min(self.size as usize);
+        assert!(start <= end);
+        let mut start_block = start / BLOCK_SZ;
+        let mut write_size = 0usize;
+        loop {
+            let mut end_current_block = (start / BLOCK_SZ + 1) * BLOCK_SZ;
+            end_current_block = end_current_block.min(end);
+            let block_write_size = end_current_block - start;
+            get_block_cache_async(
+                self.get_block_id(start_block as u32, block_device) as usize,
+                Arc::clone(block_device),
+            )
+            .await
+            .lock()
+            .modify(0, |data_block: &mut DataBlock| {
+                let src = &buf[write_size..write_size + block_write_size];
+                let dst = &mut data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_write_size];
+                dst.copy_from_slice(src);
+            });
+            write_size += block_write_size;
+            if end_current_block == end {
+                break;
+            }
+            start_block += 1;
+            start = end_current_block;
+        }
+        write_size
+    }
+}
+
+/// A directory entry, stored inline in a directory's data
+#[repr(C)]
+pub struct DirEntry {
+    name: [u8; NAME_LENGTH_LIMIT + 1],
+    inode_id: u32,
+}
+
+/// on-disk size of a single directory entry
+pub const DIRENT_SZ: usize = 32;
+
+impl DirEntry {
+    /// an all-zero directory entry, used both as scratch space and as the tombstone `unlink` writes
+    pub fn empty() -> Self {
+        Self {
+            name: [0u8; NAME_LENGTH_LIMIT + 1],
+            inode_id: 0,
+        }
+    }
+
+    /// Create a directory entry pointing at `inode_id`
+    pub fn new(name: &str, inode_id: u32) -> Self {
+        let mut bytes = [0u8; NAME_LENGTH_LIMIT + 1];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Self {
+            name: bytes,
+            inode_id,
+        }
+    }
+
+    /// view the entry as raw bytes, for reading it off disk
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const _ as usize as *const u8, DIRENT_SZ) }
+    }
+
+    /// view the entry as raw mutable bytes, for writing it to disk
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self as *mut _ as usize as *mut u8, DIRENT_SZ) }
+    }
+
+    /// the entry's file name
+    pub fn name(&self) -> &str {
+        let len = (0usize..).find(|i| self.name[*i] == 0).unwrap();
+        core::str::from_utf8(&self.name[..len]).unwrap()
+    }
+
+    /// the inode id the entry points at
+    pub fn inode_id(&self) -> u32 {
+        self.inode_id
+    }
+}
@@ -0,0 +1,25 @@
+//! An easy file system isolated from the kernel, following the same layered design as the
+//! original rCore tutorial: block device -> block cache -> bitmap/layout -> EasyFileSystem -> vfs.
+#![no_std]
+
+extern crate alloc;
+
+mod bitmap;
+mod block_cache;
+mod block_dev;
+mod efs;
+mod layout;
+mod vfs;
+
+pub use bitmap::Bitmap;
+pub use block_cache::{
+    block_cache_sync_all, block_cache_sync_all_async, get_block_cache, get_block_cache_async,
+    BlockCache,
+};
+pub use block_dev::{poll_pending_block_io, AsyncBlockDevice, BlockDevice, BlockIoFuture};
+pub use efs::EasyFileSystem;
+pub use layout::*;
+pub use vfs::Inode;
+
+/// size in bytes of a single block on the block device
+pub const BLOCK_SZ: usize = 512;
@@ -1,12 +1,17 @@
 use super::{
-    block_cache_sync_all, get_block_cache, BlockDevice, DirEntry, DiskInode, DiskInodeType,
-    EasyFileSystem, DIRENT_SZ,
+    block_cache_sync_all, block_cache_sync_all_async, get_block_cache, BlockDevice, DirEntry,
+    DiskInode, DiskInodeType, EasyFileSystem, DIRENT_SZ,
 };
 use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 use spin::{Mutex, MutexGuard};
 
+/// Maximum number of symlinks followed while resolving a single path, mirroring the
+/// `ELOOP`-style guard most Unix-likes use to stop a cycle of symlinks from looping forever.
+const MAX_SYMLINK_FOLLOW: usize = 8;
+
 pub struct Inode {
     block_id: usize,
     block_offset: usize,
@@ -74,6 +79,17 @@ impl Inode {
         })
     }
 
+    /// `(mode, uid, gid)` of this inode, for `fstat` to surface through `Stat`
+    pub fn owner(&self) -> (u16, u32, u32) {
+        self.read_disk_inode(|disk_inode| (disk_inode.mode, disk_inode.uid, disk_inode.gid))
+    }
+
+    /// Check `requested` (combination of [`crate::R_OK`]/[`crate::W_OK`]/[`crate::X_OK`])
+    /// against this inode's owner and mode bits.
+    pub fn check_access(&self, uid: u32, gid: u32, requested: u8) -> bool {
+        self.read_disk_inode(|disk_inode| disk_inode.check_access(uid, gid, requested))
+    }
+
     fn increase_size(
         &self,
         new_size: u32,
@@ -91,9 +107,129 @@ impl Inode {
         disk_inode.increase_size(new_size, v, &self.block_device);
     }
 
+    /// Create a subdirectory under `self`, seeding it with `.`/`..` dirents before linking it
+    /// into `self` so a reader never observes a directory missing its own entries.
+    pub fn create_dir(&self, name: &str, uid: u32, gid: u32) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.lock();
+        let op = |root_inode: &mut DiskInode| {
+            assert!(root_inode.is_dir());
+            self.find_inode_id(name, root_inode)
+        };
+        if self.modify_disk_inode(op).is_some() {
+            return None;
+        }
+        let new_inode_id = fs.alloc_inode();
+        let (new_inode_block_id, new_inode_block_offset) = fs.get_disk_inode_pos(new_inode_id);
+        get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
+                new_inode.initialize(DiskInodeType::Directory, 0o755, uid, gid);
+            });
+        let parent_id = fs.get_inode_id(self.block_id, self.block_offset);
+        let new_dir = Self::new(
+            new_inode_block_id,
+            new_inode_block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+        );
+        new_dir.modify_disk_inode(|dir_inode| {
+            new_dir.increase_size(2 * DIRENT_SZ as u32, dir_inode, &mut fs);
+            let dot = DirEntry::new(".", new_inode_id);
+            dir_inode.write_at(0, dot.as_bytes(), &new_dir.block_device);
+            let dotdot = DirEntry::new("..", parent_id);
+            dir_inode.write_at(DIRENT_SZ, dotdot.as_bytes(), &new_dir.block_device);
+        });
+        // link the new directory into this one's entries
+        self.modify_disk_inode(|root_inode| {
+            let file_count = (root_inode.size as usize) / DIRENT_SZ;
+            let new_size = (file_count + 1) * DIRENT_SZ;
+            self.increase_size(new_size as u32, root_inode, &mut fs);
+            let dirent = DirEntry::new(name, new_inode_id);
+            root_inode.write_at(file_count * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+        });
+        block_cache_sync_all();
+        Some(Arc::new(new_dir))
+    }
+
+    /// Resolve a `/`-separated path by walking each component through `find`, starting at
+    /// `self` (which must be a directory). Mirrors a simple `user_path_at`-style resolver:
+    /// leading/trailing/doubled slashes are ignored, and any missing component fails the walk.
+    /// A component that resolves to a symlink is followed, relative to the directory that
+    /// contains it (or from the root, if the target is itself absolute), up to
+    /// [`MAX_SYMLINK_FOLLOW`] hops before giving up (an `ELOOP` analogue).
+    pub fn walk_path(&self, path: &str) -> Option<Arc<Inode>> {
+        self.walk_path_following(path, 0)
+    }
+
+    /// The filesystem's root directory, for resolving an absolute symlink target from scratch
+    /// instead of relative to whatever directory contains the symlink.
+    fn root(&self) -> Arc<Inode> {
+        Arc::new(EasyFileSystem::root_inode(&self.fs))
+    }
+
+    fn walk_path_following(&self, path: &str, depth: usize) -> Option<Arc<Inode>> {
+        if depth > MAX_SYMLINK_FOLLOW {
+            return None;
+        }
+        let mut current = Arc::new(Self::new(
+            self.block_id as u32,
+            self.block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+        ));
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            let next = current.find(component)?;
+            current = if next.is_symlink() {
+                let target = next.read_link();
+                let base = if target.starts_with('/') {
+                    current.root()
+                } else {
+                    current.clone()
+                };
+                base.walk_path_following(&target, depth + 1)?
+            } else {
+                next
+            };
+        }
+        Some(current)
+    }
+
+    /// Split `path` into the directory that should contain its final component and that
+    /// component's name, e.g. for `mkdirat`/`linkat`/`unlinkat` which create or remove a
+    /// single entry rather than resolving all the way through it. Intermediate components
+    /// that resolve to a symlink are followed (see [`Self::walk_path`]); the final component
+    /// is returned as a bare name and is never followed, since it may not exist yet.
+    pub fn walk_parent(&self, path: &str) -> Option<(Arc<Inode>, String)> {
+        let mut components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        let name = String::from(components.pop()?);
+        let mut current = Arc::new(Self::new(
+            self.block_id as u32,
+            self.block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+        ));
+        for component in components {
+            let next = current.find(component)?;
+            current = if next.is_symlink() {
+                let target = next.read_link();
+                let base = if target.starts_with('/') {
+                    current.root()
+                } else {
+                    current.clone()
+                };
+                base.walk_path_following(&target, 1)?
+            } else {
+                next
+            };
+        }
+        Some((current, name))
+    }
+
     /// create 方法可以在根目录下创建一个文件，
     /// 该方法只有根目录的 Inode 会调用：
-    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+    /// `mode`/`uid`/`gid` set the new file's owner and permission bits (see
+    /// [`DiskInode::check_access`]).
+    pub fn create(&self, name: &str, mode: u16, uid: u32, gid: u32) -> Option<Arc<Inode>> {
         let mut fs = self.fs.lock();
         let op = |root_inode: &mut DiskInode| {
             // assert it is a directory
@@ -112,7 +248,7 @@ impl Inode {
         get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
             .lock()
             .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
-                new_inode.initialize(DiskInodeType::File);
+                new_inode.initialize(DiskInodeType::File, mode, uid, gid);
             });
         self.modify_disk_inode(|root_inode| {
             // append file in the dirent
@@ -141,6 +277,102 @@ impl Inode {
         // release efs lock automatically by compiler
     }
 
+    /// Create a symbolic link named `name` under `self`, pointing at `target`. `target` is
+    /// stored verbatim as the new inode's file data and is not validated or resolved here.
+    pub fn symlink(&self, name: &str, target: &str, uid: u32, gid: u32) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.lock();
+        let op = |root_inode: &mut DiskInode| {
+            assert!(root_inode.is_dir());
+            self.find_inode_id(name, root_inode)
+        };
+        if self.modify_disk_inode(op).is_some() {
+            return None;
+        }
+        let new_inode_id = fs.alloc_inode();
+        let (new_inode_block_id, new_inode_block_offset) = fs.get_disk_inode_pos(new_inode_id);
+        get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
+                new_inode.initialize(DiskInodeType::SymLink, 0o777, uid, gid);
+            });
+        let new_link = Self::new(
+            new_inode_block_id,
+            new_inode_block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+        );
+        new_link.modify_disk_inode(|disk_inode| {
+            new_link.increase_size(target.len() as u32, disk_inode, &mut fs);
+            disk_inode.write_at(0, target.as_bytes(), &new_link.block_device);
+        });
+        self.modify_disk_inode(|root_inode| {
+            let file_count = (root_inode.size as usize) / DIRENT_SZ;
+            let new_size = (file_count + 1) * DIRENT_SZ;
+            self.increase_size(new_size as u32, root_inode, &mut fs);
+            let dirent = DirEntry::new(name, new_inode_id);
+            root_inode.write_at(file_count * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+        });
+        block_cache_sync_all();
+        Some(Arc::new(new_link))
+    }
+
+    /// whether this inode is a symbolic link
+    pub fn is_symlink(&self) -> bool {
+        self.read_disk_inode(|disk_inode| disk_inode.is_symlink())
+    }
+
+    /// read this symlink's target path, stored verbatim as its file data
+    pub fn read_link(&self) -> String {
+        self.read_disk_inode(|disk_inode| {
+            let mut buf = vec![0u8; disk_inode.size as usize];
+            disk_inode.read_at(0, &mut buf, &self.block_device);
+            String::from_utf8_lossy(&buf).into_owned()
+        })
+    }
+
+    /// 从 `offset` 开始最多读取 `count` 个有效目录项（跳过 `unlink` 留下的全零槽位），
+    /// 返回每个目录项的 `(inode_id, name, next_offset, type)`，`next_offset` 是紧跟在该项之后、
+    /// 可用于续读的目录字节偏移，供 `getdents64` 之类的流式读取在缓冲区写满时保存进度。
+    pub fn read_dirents(&self, offset: usize, count: usize) -> Vec<(u32, String, usize, DiskInodeType)> {
+        let fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+            let mut entries = Vec::new();
+            let mut slot = offset / DIRENT_SZ;
+            while slot < file_count && entries.len() < count {
+                let mut dirent = DirEntry::empty();
+                assert_eq!(
+                    disk_inode.read_at(slot * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device),
+                    DIRENT_SZ,
+                );
+                slot += 1;
+                if dirent.name().is_empty() {
+                    // zeroed-out slot left behind by unlink
+                    continue;
+                }
+                let (entry_block_id, entry_block_offset) = fs.get_disk_inode_pos(dirent.inode_id());
+                let entry_type = get_block_cache(entry_block_id as usize, self.block_device.clone())
+                    .lock()
+                    .read(entry_block_offset, |entry_disk_inode: &DiskInode| {
+                        if entry_disk_inode.is_dir() {
+                            DiskInodeType::Directory
+                        } else if entry_disk_inode.is_symlink() {
+                            DiskInodeType::SymLink
+                        } else {
+                            DiskInodeType::File
+                        }
+                    });
+                entries.push((
+                    dirent.inode_id(),
+                    String::from(dirent.name()),
+                    slot * DIRENT_SZ,
+                    entry_type,
+                ));
+            }
+            entries
+        })
+    }
+
     /// ls 方法可以收集根目录下的所有文件的文件名并以
     /// 向量的形式返回，这个方法只有根目录的 Inode 才会调用：
     pub fn ls(&self) -> Vec<String> {
@@ -167,6 +399,31 @@ impl Inode {
         self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
     }
 
+    /// Async counterpart to [`Self::read_at`]: resolves block ids from a snapshot of the inode
+    /// taken under the block cache lock, then `.await`s each data block fetch so the executor
+    /// can poll another task instead of this one blocking the hart on a cache miss.
+    pub async fn read_at_async(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let snapshot = self.read_disk_inode(|disk_inode| disk_inode.clone());
+        snapshot.read_at_async(offset, buf, &self.block_device).await
+    }
+
+    /// Async counterpart to [`Self::write_at`]. Growing the inode (bitmap allocation, pointer
+    /// setup) stays synchronous since it's quick and needs exclusive access to the `efs`; only
+    /// the actual data block writes are `.await`ed.
+    pub async fn write_at_async(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut fs = self.fs.lock();
+        let mut snapshot = self.modify_disk_inode(|disk_inode| {
+            self.increase_size((offset + buf.len()) as u32, disk_inode, &mut fs);
+            disk_inode.clone()
+        });
+        drop(fs);
+        let size = snapshot
+            .write_at_async(offset, buf, &self.block_device)
+            .await;
+        block_cache_sync_all_async().await;
+        size
+    }
+
     /// 需要注意在 DiskInode::write_at 之前先调用 increase_size 对自身进行扩容：
     /// 这里会从 EasyFileSystem 中分配一些用于扩容的数据块
     /// 并传给 DiskInode::increase_size 。
@@ -195,8 +452,9 @@ impl Inode {
         block_cache_sync_all();
     }
 
-    /// 硬链接文件
-    pub fn link(&self, name: &str, old_name: &str)  {
+    /// 硬链接文件：在本目录下为 `old_name` 指向的 inode 新增一个名为 `name` 的目录项，
+    /// 并把该 inode 的 `nlink` 计数加一，而不是依赖扫描目录来统计链接数。
+    pub fn link(&self, name: &str, old_name: &str) {
         let mut fs = self.fs.lock();
         let inode_id = self.read_disk_inode(|disk_inode| {
             self.find_inode_id(old_name, disk_inode)});
@@ -209,48 +467,35 @@ impl Inode {
             let file_count = (root_inode.size as usize) / DIRENT_SZ;
             let new_size = (file_count + 1) * DIRENT_SZ;
             self.increase_size(new_size as u32, root_inode, &mut fs);
-            
+
             let dirent = DirEntry::new(name, inode_id);
-            
+
             root_inode.write_at(
                 file_count * DIRENT_SZ,
-                 dirent.as_bytes(), 
+                 dirent.as_bytes(),
                  &self.block_device
             );
         });
+        let (target_block_id, target_block_offset) = fs.get_disk_inode_pos(inode_id);
+        get_block_cache(target_block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(target_block_offset, |target: &mut DiskInode| {
+                target.nlink += 1;
+            });
         block_cache_sync_all();
     }
 
-    /// 通过inode_id查找 direntry
-    fn find_by_inode_id(&self, inode_id: u32, disk_inode: &DiskInode) -> i32 {
-        assert!(disk_inode.is_dir());
-        let mut count = 0;
-        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
-        let mut dirent = DirEntry::empty();
-        for i in 0..file_count {
-            assert_eq!(
-                disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device,),
-                DIRENT_SZ,
-            );
-            if dirent.inode_id() == inode_id {
-                count += 1;
-            }
-        }
-        count
-    }
-
-    /// 取消硬链接文件
+    /// 取消硬链接文件：删除本目录下名为 `name` 的目录项，并把目标 inode 的 `nlink` 减一；
+    /// 只有当 `nlink` 降到 0 时才真正释放它占用的数据块。
     pub fn unlink(&self, name: &str) {
-        // let fs = self.fs.lock();
-        // let mut inode_id = self.read_disk_inode(|disk_inode| {
-        //     self.find_inode_id(name, disk_inode)}).unwrap();
         let mut inode_id = 0;
         let inode = self.find(name).unwrap();
-        
+        let mut fs = self.fs.lock();
+
         self.modify_disk_inode(|root_inode| {
             // delete file in the dirent
             let file_count = (root_inode.size as usize) / DIRENT_SZ;
-           
+
             for i in 0..file_count {
                 let mut dirent = DirEntry::empty();
                 assert_eq!(
@@ -267,13 +512,25 @@ impl Inode {
                     break;
                 }
             }
-            
+
         });
-        let count = self.hard_link_count(inode_id);
-        if count == 1{
+        let (target_block_id, target_block_offset) = fs.get_disk_inode_pos(inode_id);
+        let nlink = get_block_cache(target_block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(target_block_offset, |target: &mut DiskInode| {
+                target.nlink -= 1;
+                target.nlink
+            });
+        drop(fs);
+        if nlink == 0 {
             inode.clear();
         }
-        // block_cache_sync_all();
+        block_cache_sync_all();
+    }
+
+    /// number of hard links pointing at this inode, read directly from `DiskInode::nlink`
+    pub fn nlink(&self) -> u32 {
+        self.read_disk_inode(|disk_inode| disk_inode.nlink)
     }
 
     /// 通过inode 获得inode_id(ROOT调用)
@@ -290,12 +547,4 @@ impl Inode {
         self.block_id == inode.block_id
     }
 
-    /// inode 有几个硬链接
-    pub fn hard_link_count(&self, inode_id: u32) -> u32 {
-        let mut count = 0;
-        self.modify_disk_inode(|root_inode| {
-            count = self.find_by_inode_id(inode_id, root_inode);
-        });
-        count as u32
-    }
 }
@@ -0,0 +1,62 @@
+//! Condition variable, for use alongside a `Mutex` to build producer/consumer and barrier
+//! patterns
+
+use super::mutex::Mutex;
+use super::UPSafeCell;
+use crate::task::TaskControlBlock;
+use crate::task::{block_current_and_run_next, current_task, wakeup_task};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// Condition variable
+pub struct Condvar {
+    inner: UPSafeCell<CondvarInner>,
+}
+
+pub struct CondvarInner {
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl Condvar {
+    /// Create a new condition variable
+    pub fn new() -> Self {
+        trace!("kernel: Condvar::new");
+        Self {
+            inner: unsafe {
+                UPSafeCell::new(CondvarInner {
+                    wait_queue: VecDeque::new(),
+                })
+            },
+        }
+    }
+
+    /// Wake one task blocked in `wait`
+    pub fn signal(&self) {
+        trace!("kernel: Condvar::signal");
+        let mut inner = self.inner.exclusive_access();
+        if let Some(task) = inner.wait_queue.pop_front() {
+            wakeup_task(task);
+        }
+    }
+
+    /// Wake every task blocked in `wait`
+    pub fn broadcast(&self) {
+        trace!("kernel: Condvar::broadcast");
+        let mut inner = self.inner.exclusive_access();
+        while let Some(task) = inner.wait_queue.pop_front() {
+            wakeup_task(task);
+        }
+    }
+
+    /// Atomically unlock `mutex`, block the caller until woken, then re-lock `mutex` before
+    /// returning
+    pub fn wait(&self, mutex: Arc<dyn Mutex>) {
+        trace!("kernel: Condvar::wait");
+        mutex.unlock();
+        let mut inner = self.inner.exclusive_access();
+        inner.wait_queue.push_back(current_task().unwrap());
+        drop(inner);
+        block_current_and_run_next();
+        mutex.lock();
+    }
+}
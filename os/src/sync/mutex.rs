@@ -1,15 +1,248 @@
-//! Mutex (spin-like and blocking(sleep))
+//! Mutex (spin-like and blocking(sleep)), plus banker's-algorithm deadlock detection for the
+//! blocking flavor.
 
 use super::UPSafeCell;
+use crate::syscall::process::BIG_STRIDE;
 use crate::task::TaskControlBlock;
 use crate::task::{block_current_and_run_next, suspend_current_and_run_next};
 use crate::task::{current_task, wakeup_task};
-use alloc::{collections::VecDeque, sync::Arc};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+
+/// Returned by `Mutex::lock` in place of blocking, when deadlock detection is enabled for the
+/// calling process and the banker's-algorithm safety check rejects this request.
+pub const DEADLOCK_ERROR: isize = -0xDEAD;
+
+/// Runs the banker's algorithm: starting from `available`, can every row in `allocation`/`need`
+/// eventually finish and hand its resources back? Used before granting a blocking lock so the
+/// kernel can refuse a request that would only be satisfiable from an unsafe state, instead of
+/// letting it potentially block forever.
+pub fn is_safe_state(available: &[usize], allocation: &[Vec<usize>], need: &[Vec<usize>]) -> bool {
+    let mut work = available.to_vec();
+    let mut finished = vec![false; allocation.len()];
+    loop {
+        let mut progressed = false;
+        for i in 0..finished.len() {
+            if finished[i] {
+                continue;
+            }
+            if need[i].iter().zip(work.iter()).all(|(n, a)| n <= a) {
+                for (w, a) in work.iter_mut().zip(allocation[i].iter()) {
+                    *w += a;
+                }
+                finished[i] = true;
+                progressed = true;
+            }
+        }
+        if !progressed {
+            break;
+        }
+    }
+    finished.into_iter().all(|done| done)
+}
+
+/// Per-process (keyed by pid) banker's-algorithm bookkeeping: a `total` row per resource id that
+/// process has touched, plus a per-thread `allocation`/`need` row keyed by `(pid, tid)`, plus
+/// whether that process opted into detection via `sys_enable_deadlock_detect`. Resource ids are
+/// local to each process (e.g. the index among that process's `sys_mutex_create`/
+/// `sys_semaphore_create` calls, sharing one id space so a mutex and a semaphore never collide),
+/// so `total` is keyed by pid alone — but `allocation`/`need` must be keyed by `(pid, tid)`: two
+/// threads of one process contending for the same mutex are two separate banker's-algorithm
+/// participants, each with their own row, not one combined row pretending to be the whole
+/// system (collapsing them would make `is_safe` reject the ordinary "thread B blocks on a mutex
+/// thread A holds" case, since B's own combined need would never fit in what's left after A's
+/// own combined allocation).
+///
+/// This lives as shared global state rather than fields on `TaskControlBlockInner` because the
+/// tasks racing for the *same* resource (e.g. a parent and a forked child sharing an inherited
+/// mutex/semaphore handle) are distinct TCBs that all need to be visible to one safety check at
+/// once.
+struct DeadlockDetector {
+    enabled: BTreeMap<usize, bool>,
+    total: BTreeMap<usize, Vec<usize>>,
+    allocation: BTreeMap<(usize, usize), Vec<usize>>,
+    need: BTreeMap<(usize, usize), Vec<usize>>,
+}
+
+impl DeadlockDetector {
+    fn new() -> Self {
+        Self {
+            enabled: BTreeMap::new(),
+            total: BTreeMap::new(),
+            allocation: BTreeMap::new(),
+            need: BTreeMap::new(),
+        }
+    }
+
+    fn is_enabled(&self, pid: usize) -> bool {
+        *self.enabled.get(&pid).unwrap_or(&false)
+    }
+
+    fn set_enabled(&mut self, pid: usize, enabled: bool) {
+        self.enabled.insert(pid, enabled);
+    }
+
+    fn row_mut(map: &mut BTreeMap<usize, Vec<usize>>, pid: usize, width: usize) -> &mut Vec<usize> {
+        let row = map.entry(pid).or_insert_with(Vec::new);
+        if row.len() < width {
+            row.resize(width, 0);
+        }
+        row
+    }
+
+    fn thread_row_mut(
+        map: &mut BTreeMap<(usize, usize), Vec<usize>>,
+        pid: usize,
+        tid: usize,
+        width: usize,
+    ) -> &mut Vec<usize> {
+        let row = map.entry((pid, tid)).or_insert_with(Vec::new);
+        if row.len() < width {
+            row.resize(width, 0);
+        }
+        row
+    }
+
+    /// Record `id`'s total unit count for `pid` (`1` for a binary `Mutex`, `res_count` for a
+    /// counting `Semaphore`). Called once, from the resource's constructor.
+    fn register_resource(&mut self, pid: usize, id: usize, total: usize) {
+        Self::row_mut(&mut self.total, pid, id + 1)[id] = total;
+    }
+
+    fn width(&self, pid: usize) -> usize {
+        self.total.get(&pid).map_or(0, Vec::len)
+    }
+
+    /// Record whether thread `tid` of process `pid` is currently blocked wanting one unit of
+    /// `id`.
+    fn set_need(&mut self, pid: usize, tid: usize, id: usize, needed: bool) {
+        let width = self.width(pid).max(id + 1);
+        Self::thread_row_mut(&mut self.need, pid, tid, width)[id] = needed as usize;
+    }
+
+    /// Record that thread `tid` of process `pid` now holds (`delta > 0`) or has released
+    /// (`delta < 0`) one unit of `id`.
+    fn add_allocation(&mut self, pid: usize, tid: usize, id: usize, delta: isize) {
+        let width = self.width(pid).max(id + 1);
+        let row = Self::thread_row_mut(&mut self.allocation, pid, tid, width);
+        row[id] = (row[id] as isize + delta).max(0) as usize;
+    }
+
+    /// Every tid of `pid` that has ever touched `allocation` or `need`, i.e. every thread the
+    /// safety check needs its own row for.
+    fn known_tids(&self, pid: usize) -> Vec<usize> {
+        let mut tids: Vec<usize> = self
+            .allocation
+            .keys()
+            .chain(self.need.keys())
+            .filter(|(p, _)| *p == pid)
+            .map(|(_, tid)| *tid)
+            .collect();
+        tids.sort_unstable();
+        tids.dedup();
+        tids
+    }
+
+    /// Free units of `pid`'s resources: `total` minus what every thread of `pid` currently
+    /// holds, combined.
+    ///
+    /// Resource ids are process-local, so only `pid`'s own allocations compete for `pid`'s own
+    /// totals — unlike pid, resource id `0` in one process has nothing to do with resource id
+    /// `0` in another.
+    fn available(&self, pid: usize) -> Vec<usize> {
+        let mut avail = self.total.get(&pid).cloned().unwrap_or_default();
+        for ((p, _tid), row) in self.allocation.iter() {
+            if *p != pid {
+                continue;
+            }
+            for (a, held) in avail.iter_mut().zip(row.iter()) {
+                *a = a.saturating_sub(*held);
+            }
+        }
+        avail
+    }
+
+    /// Would the current allocation/need rows still let every thread of `pid` eventually finish?
+    /// Consulted right after a thread's need row for a resource is set, before it joins the wait
+    /// queue — evaluates one row per thread that has touched these matrices, not one row for the
+    /// whole process, since it's threads, not processes, that independently hold and request
+    /// resources.
+    fn is_safe(&self, pid: usize) -> bool {
+        let width = self.width(pid);
+        let tids = self.known_tids(pid);
+        let row_for = |map: &BTreeMap<(usize, usize), Vec<usize>>, tid: usize| {
+            map.get(&(pid, tid))
+                .cloned()
+                .map(|mut row| {
+                    row.resize(width, 0);
+                    row
+                })
+                .unwrap_or_else(|| vec![0; width])
+        };
+        let allocation: Vec<Vec<usize>> =
+            tids.iter().map(|tid| row_for(&self.allocation, *tid)).collect();
+        let need: Vec<Vec<usize>> = tids.iter().map(|tid| row_for(&self.need, *tid)).collect();
+        is_safe_state(&self.available(pid), &allocation, &need)
+    }
+}
+
+lazy_static! {
+    /// Shared global instance of the deadlock detector
+    static ref DEADLOCK_DETECTOR: UPSafeCell<DeadlockDetector> =
+        unsafe { UPSafeCell::new(DeadlockDetector::new()) };
+}
+
+/// Enable or disable deadlock detection for process `pid`; backs `sys_enable_deadlock_detect`.
+pub fn set_deadlock_detect_enabled(pid: usize, enabled: bool) {
+    DEADLOCK_DETECTOR.exclusive_access().set_enabled(pid, enabled);
+}
+
+/// Register `id`'s total unit count for `pid`'s deadlock-detection matrices; call once from a
+/// `Mutex`/`Semaphore` constructor.
+pub(crate) fn register_resource(pid: usize, id: usize, total: usize) {
+    DEADLOCK_DETECTOR
+        .exclusive_access()
+        .register_resource(pid, id, total);
+}
+
+/// Try to reserve one unit of `id` for thread `tid` of process `pid`: records the request in
+/// `need`, and if deadlock detection is enabled for `pid` and granting it would leave the system
+/// unsafe, clears the request and returns `false` instead of letting the caller enqueue. On
+/// `true`, `need` is left set until the caller pairs this with [`finish_request`].
+pub(crate) fn begin_request(pid: usize, tid: usize, id: usize) -> bool {
+    let mut detector = DEADLOCK_DETECTOR.exclusive_access();
+    detector.set_need(pid, tid, id, true);
+    if detector.is_enabled(pid) && !detector.is_safe(pid) {
+        detector.set_need(pid, tid, id, false);
+        return false;
+    }
+    true
+}
+
+/// Pair with a granted [`begin_request`]: clears the pending `need` flag and records the unit as
+/// allocated to thread `tid`.
+pub(crate) fn finish_request(pid: usize, tid: usize, id: usize) {
+    let mut detector = DEADLOCK_DETECTOR.exclusive_access();
+    detector.set_need(pid, tid, id, false);
+    detector.add_allocation(pid, tid, id, 1);
+}
+
+/// Record that thread `tid` of process `pid` released one unit of `id`.
+pub(crate) fn release_resource(pid: usize, tid: usize, id: usize) {
+    DEADLOCK_DETECTOR
+        .exclusive_access()
+        .add_allocation(pid, tid, id, -1);
+}
 
 /// Mutex trait
 pub trait Mutex: Sync + Send {
-    /// Lock the mutex
-    fn lock(&self);
+    /// Lock the mutex. Returns `0` once acquired, or [`DEADLOCK_ERROR`] if deadlock detection is
+    /// enabled for the calling process and the banker's-algorithm safety check rejects this
+    /// request instead of letting it block.
+    fn lock(&self) -> isize;
     /// Unlock the mutex
     fn unlock(&self) -> isize;
 }
@@ -29,8 +262,9 @@ impl MutexSpin {
 }
 
 impl Mutex for MutexSpin {
-    /// Lock the spinlock mutex
-    fn lock(&self) {
+    /// Lock the spinlock mutex. Spinlocks never join a wait queue, so they sit outside
+    /// deadlock detection (same as the upstream banker's-algorithm lab scope).
+    fn lock(&self) -> isize {
         trace!("kernel: MutexSpin::lock");
         loop {
             let mut locked = self.locked.exclusive_access();
@@ -40,7 +274,7 @@ impl Mutex for MutexSpin {
                 continue;
             } else {
                 *locked = true;
-                return;
+                return 0;
             }
         }
     }
@@ -53,24 +287,63 @@ impl Mutex for MutexSpin {
     }
 }
 
+/// Raise `holder`'s effective priority to `priority` if that's higher than what it currently
+/// has, recomputing its `pass` so the scheduler advances it faster. Follows `blocked_on_holder`
+/// transitively, so a chain of tasks each blocked on the next also gets the donation — donation
+/// stops as soon as a link in the chain is already at or above `priority`.
+fn donate_priority(holder: &Arc<TaskControlBlock>, priority: usize) {
+    let mut current = holder.clone();
+    loop {
+        let next = {
+            let mut inner = current.inner_exclusive_access();
+            if priority <= inner.priority {
+                return;
+            }
+            inner.priority = priority;
+            inner.pass = BIG_STRIDE / inner.priority;
+            inner.blocked_on_holder.clone()
+        };
+        match next {
+            Some(next_holder) => current = next_holder,
+            None => return,
+        }
+    }
+}
+
+/// Drop any priority donated to `task` and recompute `pass` from its `base_priority`
+fn restore_priority(task: &Arc<TaskControlBlock>) {
+    let mut inner = task.inner_exclusive_access();
+    inner.priority = inner.base_priority;
+    inner.pass = BIG_STRIDE / inner.priority;
+}
+
 /// Blocking Mutex struct
 pub struct MutexBlocking {
+    /// resource id this mutex is keyed by in the owning process's deadlock-detection matrices
+    /// (its index among that process's `sys_mutex_create` calls)
+    id: usize,
     inner: UPSafeCell<MutexBlockingInner>,
 }
 
 pub struct MutexBlockingInner {
     locked: bool,
+    /// task currently holding the mutex, so a newly-blocked waiter can donate its priority to
+    /// avoid priority inversion
+    holder: Option<Arc<TaskControlBlock>>,
     wait_queue: VecDeque<Arc<TaskControlBlock>>,
 }
 
 impl MutexBlocking {
-    /// Create a new blocking mutex
-    pub fn new() -> Self {
+    /// Create a new blocking mutex identified by `id`
+    pub fn new(id: usize) -> Self {
         trace!("kernel: MutexBlocking::new");
+        register_resource(current_task().unwrap().inner_exclusive_access().process_pid, id, 1);
         Self {
+            id,
             inner: unsafe {
                 UPSafeCell::new(MutexBlockingInner {
                     locked: false,
+                    holder: None,
                     wait_queue: VecDeque::new(),
                 })
             },
@@ -79,17 +352,38 @@ impl MutexBlocking {
 }
 
 impl Mutex for MutexBlocking {
-    /// lock the blocking mutex
-    fn lock(&self) {
+    /// lock the blocking mutex. If it's already held, this would block — but if deadlock
+    /// detection is enabled for the caller, first run the banker's-algorithm safety check with
+    /// this request's `need` factored in, and refuse with [`DEADLOCK_ERROR`] instead of
+    /// enqueueing if the result would be unsafe. Blocking also donates this waiter's priority to
+    /// the holder (transitively, if the holder is itself blocked elsewhere) so a low-priority
+    /// holder can't starve a high-priority waiter under stride scheduling.
+    fn lock(&self) -> isize {
         trace!("kernel: MutexBlocking::lock");
+        let task = current_task().unwrap();
+        let pid = task.inner_exclusive_access().process_pid;
+        let tid = task.inner_exclusive_access().res.as_ref().unwrap().tid;
         let mut mutex_inner = self.inner.exclusive_access();
         if mutex_inner.locked {
-            mutex_inner.wait_queue.push_back(current_task().unwrap());
+            if !begin_request(pid, tid, self.id) {
+                return DEADLOCK_ERROR;
+            }
+            let priority = task.inner_exclusive_access().priority;
+            if let Some(holder) = mutex_inner.holder.clone() {
+                donate_priority(&holder, priority);
+            }
+            task.inner_exclusive_access().blocked_on_holder = mutex_inner.holder.clone();
+            mutex_inner.wait_queue.push_back(task.clone());
             drop(mutex_inner);
             block_current_and_run_next();
+            task.inner_exclusive_access().blocked_on_holder = None;
+            finish_request(pid, tid, self.id);
         } else {
             mutex_inner.locked = true;
+            mutex_inner.holder = Some(task.clone());
+            finish_request(pid, tid, self.id);
         }
+        0
     }
 
     /// unlock the blocking mutex
@@ -97,7 +391,21 @@ impl Mutex for MutexBlocking {
         trace!("kernel: MutexBlocking::unlock");
         let mut mutex_inner = self.inner.exclusive_access();
         assert!(mutex_inner.locked);
+        let current = current_task().unwrap();
+        let pid = current.inner_exclusive_access().process_pid;
+        let tid = current.inner_exclusive_access().res.as_ref().unwrap().tid;
+        release_resource(pid, tid, self.id);
+        if let Some(old_holder) = mutex_inner.holder.take() {
+            restore_priority(&old_holder);
+        }
         if let Some(waking_task) = mutex_inner.wait_queue.pop_front() {
+            // the remaining waiters may already be donating a higher priority than this one
+            // brought with it; re-derive the new holder's priority from all of them
+            for waiter in mutex_inner.wait_queue.iter() {
+                let priority = waiter.inner_exclusive_access().priority;
+                donate_priority(&waking_task, priority);
+            }
+            mutex_inner.holder = Some(waking_task.clone());
             let tid = waking_task.inner_exclusive_access().res.as_ref().unwrap().tid;
             wakeup_task(waking_task);
             return tid as isize;
@@ -0,0 +1,76 @@
+//! Counting semaphore, built on the same wait-queue pattern as `MutexBlocking`
+
+use super::mutex::{begin_request, finish_request, register_resource, release_resource, DEADLOCK_ERROR};
+use super::UPSafeCell;
+use crate::task::TaskControlBlock;
+use crate::task::{block_current_and_run_next, current_task, wakeup_task};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// Counting semaphore identified by `id` in its owning process's deadlock-detection matrices
+pub struct Semaphore {
+    /// resource id this semaphore is keyed by (shares an id space with that process's mutexes)
+    id: usize,
+    inner: UPSafeCell<SemaphoreInner>,
+}
+
+pub struct SemaphoreInner {
+    count: isize,
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl Semaphore {
+    /// Create a new semaphore identified by `id`, with `res_count` initially-available units
+    pub fn new(id: usize, res_count: usize) -> Self {
+        trace!("kernel: Semaphore::new");
+        register_resource(current_task().unwrap().inner_exclusive_access().process_pid, id, res_count);
+        Self {
+            id,
+            inner: unsafe {
+                UPSafeCell::new(SemaphoreInner {
+                    count: res_count as isize,
+                    wait_queue: VecDeque::new(),
+                })
+            },
+        }
+    }
+
+    /// Release one unit, waking a queued task if one is waiting
+    pub fn up(&self) {
+        trace!("kernel: Semaphore::up");
+        let mut inner = self.inner.exclusive_access();
+        let current = current_task().unwrap();
+        let pid = current.inner_exclusive_access().process_pid;
+        let tid = current.inner_exclusive_access().res.as_ref().unwrap().tid;
+        release_resource(pid, tid, self.id);
+        inner.count += 1;
+        if inner.count <= 0 {
+            if let Some(task) = inner.wait_queue.pop_front() {
+                wakeup_task(task);
+            }
+        }
+    }
+
+    /// Acquire one unit, blocking if none are available. Returns [`DEADLOCK_ERROR`] instead of
+    /// blocking if deadlock detection is enabled for the caller and granting this request would
+    /// leave the system unsafe.
+    pub fn down(&self) -> isize {
+        trace!("kernel: Semaphore::down");
+        let task = current_task().unwrap();
+        let pid = task.inner_exclusive_access().process_pid;
+        let tid = task.inner_exclusive_access().res.as_ref().unwrap().tid;
+        let mut inner = self.inner.exclusive_access();
+        inner.count -= 1;
+        if inner.count < 0 {
+            if !begin_request(pid, tid, self.id) {
+                inner.count += 1;
+                return DEADLOCK_ERROR;
+            }
+            inner.wait_queue.push_back(task.clone());
+            drop(inner);
+            block_current_and_run_next();
+        }
+        finish_request(pid, tid, self.id);
+        0
+    }
+}
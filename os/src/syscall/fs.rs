@@ -1,9 +1,19 @@
 //! File and filesystem-related syscalls
 
-use crate::fs::{fstat, link, open_file, unlink, OSInode, OpenFlags, Stat};
+use crate::fs::{
+    fstat, link, mkdirat, open_file, readlink, symlinkat, unlink, OSInode, OpenFlags, Stat, R_OK,
+    W_OK,
+};
 use crate::mm::{translated_byte_buffer, translated_refmut, translated_str, UserBuffer};
 use crate::task::{current_task, current_user_token};
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use easy_fs::DiskInodeType;
+
+/// `d_type` values `sys_getdents64` fills in, matching Linux's `dirent64::d_type`
+const DT_REG: u8 = 8;
+const DT_DIR: u8 = 4;
+const DT_LNK: u8 = 10;
 
 
 /// buf:缓冲区
@@ -13,15 +23,17 @@ pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
     let token = current_user_token();
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
+    let fd_table = inner.fd_table.exclusive_access();
+    if fd >= fd_table.len() {
         return -1;
     }
-    if let Some(file) = &inner.fd_table[fd] {
+    if let Some(file) = &fd_table[fd] {
         if !file.writable() {
             return -1;
         }
         let file = file.clone();
         // release current task TCB manually to avoid multi-borrow
+        drop(fd_table);
         drop(inner);
         file.write(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
     } else {
@@ -34,15 +46,17 @@ pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
     let token = current_user_token();
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
+    let fd_table = inner.fd_table.exclusive_access();
+    if fd >= fd_table.len() {
         return -1;
     }
-    if let Some(file) = &inner.fd_table[fd] {
+    if let Some(file) = &fd_table[fd] {
         let file = file.clone();
         if !file.readable() {
             return -1;
         }
         // release current task TCB manually to avoid multi-borrow
+        drop(fd_table);
         drop(inner);
         trace!("kernel: sys_read .. file.read");
         file.read(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
@@ -56,30 +70,172 @@ pub fn sys_open(path: *const u8, flags: u32) -> isize {
     let task = current_task().unwrap();
     let token = current_user_token();
     let path = translated_str(token, path);
-    if let Some(inode) = open_file(path.as_str(), OpenFlags::from_bits(flags).unwrap()) {
+    let open_flags = OpenFlags::from_bits(flags).unwrap();
+    if let Some(inode) = open_file(path.as_str(), open_flags) {
         let mut inner = task.inner_exclusive_access();
+        let requested = if open_flags.contains(OpenFlags::WRONLY) || open_flags.contains(OpenFlags::RDWR) {
+            W_OK
+        } else {
+            R_OK
+        };
+        if !inode.check_access(inner.uid, inner.gid, requested) {
+            return -1;
+        }
         let fd = inner.alloc_fd();
-        inner.fd_table[fd] = Some(inode);
+        inner.fd_table.exclusive_access()[fd] = Some(inode);
         fd as isize
     } else {
         -1
     }
 }
 
+/// seek from the start of the file
+pub const SEEK_SET: usize = 0;
+/// seek relative to the current offset
+pub const SEEK_CUR: usize = 1;
+/// seek relative to the end of the file
+pub const SEEK_END: usize = 2;
+
+/// Reposition an open file's read/write offset, mirroring `lseek(2)`.
+/// Returns the resulting absolute offset, or `-1` if `fd`/`whence` is invalid or the
+/// resulting offset would be negative.
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    trace!("kernel:pid[{}] sys_lseek", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let fd_table = inner.fd_table.exclusive_access();
+    if fd >= fd_table.len() {
+        return -1;
+    }
+    let file = match &fd_table[fd] {
+        Some(file) => file.clone(),
+        None => return -1,
+    };
+    drop(fd_table);
+    drop(inner);
+    let inode = unsafe { &*(Arc::as_ptr(&file) as *const OSInode) };
+    let new_offset = match whence {
+        SEEK_SET => offset,
+        SEEK_CUR => inode.offset() as isize + offset,
+        SEEK_END => inode.read_disk_inode(|di| di.size) as isize + offset,
+        _ => return -1,
+    };
+    if new_offset < 0 {
+        return -1;
+    }
+    inode.set_offset(new_offset as usize);
+    new_offset as isize
+}
+
+/// Read a chunk of directory entries from `fd` into `buf`, resuming from the fd's stored
+/// offset across calls so a large directory can be streamed without loading it all into
+/// kernel memory at once. Each record is `d_ino: u64 | d_reclen: u16 | d_type: u8 | name\0`.
+/// Returns the number of bytes written, 0 at end-of-directory, or `-1` on an invalid fd.
+pub fn sys_getdents64(fd: usize, buf: *mut u8, len: usize) -> isize {
+    trace!("kernel:pid[{}] sys_getdents64", current_task().unwrap().pid.0);
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let fd_table = inner.fd_table.exclusive_access();
+    if fd >= fd_table.len() {
+        return -1;
+    }
+    let file = match &fd_table[fd] {
+        Some(file) => file.clone(),
+        None => return -1,
+    };
+    drop(fd_table);
+    drop(inner);
+    let inode = unsafe { &*(Arc::as_ptr(&file) as *const OSInode) };
+    let entries = inode.read_dirents(inode.offset(), usize::MAX);
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut resume_offset = inode.offset();
+    for (inode_id, name, next_offset, entry_type) in entries {
+        let name_bytes = name.as_bytes();
+        // d_ino + d_reclen + d_type + name + NUL terminator
+        let reclen = 8 + 2 + 1 + name_bytes.len() + 1;
+        if bytes.len() + reclen > len {
+            break;
+        }
+        bytes.extend_from_slice(&(inode_id as u64).to_le_bytes());
+        bytes.extend_from_slice(&(reclen as u16).to_le_bytes());
+        bytes.push(match entry_type {
+            DiskInodeType::Directory => DT_DIR,
+            DiskInodeType::SymLink => DT_LNK,
+            DiskInodeType::File => DT_REG,
+        });
+        bytes.extend_from_slice(name_bytes);
+        bytes.push(0);
+        resume_offset = next_offset;
+    }
+    UserBuffer::new(translated_byte_buffer(token, buf, bytes.len())).write(&bytes);
+    inode.set_offset(resume_offset);
+    bytes.len() as isize
+}
+
 pub fn sys_close(fd: usize) -> isize {
     trace!("kernel:pid[{}] sys_close", current_task().unwrap().pid.0);
     let task = current_task().unwrap();
-    let mut inner = task.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
+    let inner = task.inner_exclusive_access();
+    let mut fd_table = inner.fd_table.exclusive_access();
+    if fd >= fd_table.len() {
         return -1;
     }
-    if inner.fd_table[fd].is_none() {
+    if fd_table[fd].is_none() {
         return -1;
     }
-    inner.fd_table[fd].take();
+    fd_table[fd].take();
     0
 }
 
+/// Create a directory at `path`, creating it relative to its parent directory so nested
+/// paths like `/foo/bar/baz` can be built up one `mkdirat` at a time.
+pub fn sys_mkdirat(path: *const u8) -> isize {
+    trace!("kernel:pid[{}] sys_mkdirat", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let uid = task.inner_exclusive_access().uid;
+    let gid = task.inner_exclusive_access().gid;
+    if mkdirat(path.as_str(), uid, gid) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Create a symbolic link at `linkpath` whose target is the literal string `target` (not
+/// resolved or validated against the filesystem at creation time), mirroring `symlinkat(2)`.
+pub fn sys_symlinkat(target: *const u8, linkpath: *const u8) -> isize {
+    trace!("kernel:pid[{}] sys_symlinkat", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let token = current_user_token();
+    let target = translated_str(token, target);
+    let linkpath = translated_str(token, linkpath);
+    let uid = task.inner_exclusive_access().uid;
+    let gid = task.inner_exclusive_access().gid;
+    if symlinkat(target.as_str(), linkpath.as_str(), uid, gid) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Read the target of the symlink at `path` into `buf`, mirroring `readlink(2)`. Returns the
+/// number of bytes written, or `-1` if `path` doesn't exist or isn't a symlink.
+pub fn sys_readlink(path: *const u8, buf: *mut u8, len: usize) -> isize {
+    trace!("kernel:pid[{}] sys_readlink", current_task().unwrap().pid.0);
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    match readlink(path.as_str()) {
+        Some(target) => {
+            let n = target.len().min(len);
+            UserBuffer::new(translated_byte_buffer(token, buf, n)).write(&target.as_bytes()[..n]);
+            n as isize
+        }
+        None => -1,
+    }
+}
 
 /// YOUR JOB: Implement fstat.
 pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
@@ -87,8 +243,9 @@ pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
     let task = current_task().unwrap();
     let token = current_user_token();
     let inner = task.inner_exclusive_access();
+    let fd_table = inner.fd_table.exclusive_access();
     let inode = unsafe {
-        &*(Arc::as_ptr(inner.fd_table[fd].as_ref().unwrap()) as *const OSInode)
+        &*(Arc::as_ptr(fd_table[fd].as_ref().unwrap()) as *const OSInode)
     };
     let st_info = translated_refmut(token, st);
     fstat(inode, st_info);
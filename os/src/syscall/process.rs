@@ -1,19 +1,32 @@
 //! Process management syscalls
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bitflags::bitflags;
 
 use crate::{
+    config::MAX_SYSCALL_NUM,
     loader::get_app_data_by_name,
     timer::get_time_us,
-    mm::{translated_refmut, translated_str, VirtAddr},
+    mm::{translated_byte_buffer, translated_refmut, translated_str, UserBuffer, VirtAddr},
+    sync::set_deadlock_detect_enabled,
     task::{
         add_task, current_task, current_user_token, exit_current_and_run_next,
-        suspend_current_and_run_next, TaskInfo, get_current_task_info,
-        mmap, munmap
+        suspend_current_and_run_next, TaskInfo, TaskStatus, get_current_task_info,
+        mmap, munmap, SeccompAction, SeccompFilter,
     },
+    trap::TrapContext,
 };
 
-/// P.pass = BigStride / P.priority 
-pub const BIG_STRIDE : usize = 1000;
+/// P.pass = BigStride / P.priority
+///
+/// Large enough that the spread between any two ready tasks' strides stays well within a
+/// `usize`'s range between scheduling decisions, which is what lets `TaskManager::fetch`
+/// compare strides with a wrapping subtraction instead of needing them to never overflow.
+pub const BIG_STRIDE : usize = 0xFFFF_FFFF;
+
+/// Default priority newly created tasks start at (so stride scheduling is meaningful before
+/// any `sys_set_priority` call).
+pub const DEFAULT_PRIORITY: usize = 16;
 
 #[repr(C)]
 #[derive(Debug)]
@@ -22,6 +35,39 @@ pub struct TimeVal {
     pub usec: usize,
 }
 
+impl TimeVal {
+    fn from_us(us: usize) -> Self {
+        Self {
+            sec: us / 1_000_000,
+            usec: us % 1_000_000,
+        }
+    }
+}
+
+bitflags! {
+    /// `sys_waitpid`'s `options` argument, mirroring the subset of POSIX `waitpid(2)`'s `options`
+    /// this kernel acts on.
+    pub struct WaitOption: usize {
+        /// Return immediately (with `0`) if the matching child exists but hasn't exited yet,
+        /// instead of the caller having to busy-spin on the `-2` sentinel itself.
+        const WNOHANG = 1;
+    }
+}
+
+/// Resource usage accounting, mirroring POSIX `getrusage(2)`'s `rusage` down to the two fields
+/// this kernel tracks.
+#[repr(C)]
+#[derive(Debug)]
+pub struct RUsage {
+    /// Time spent executing in user mode
+    pub utime: TimeVal,
+    /// Time spent executing in kernel mode
+    pub stime: TimeVal,
+}
+
+/// `who` meaning "the calling task itself", the only value `sys_getrusage` currently supports
+pub const RUSAGE_SELF: isize = 0;
+
 
 /// task exits and submit an exit code
 pub fn sys_exit(exit_code: i32) -> ! {
@@ -39,24 +85,62 @@ pub fn sys_yield() -> isize {
 
 pub fn sys_getpid() -> isize {
     trace!("kernel: sys_getpid pid:{}", current_task().unwrap().pid.0);
-    current_task().unwrap().pid.0 as isize
+    current_task().unwrap().inner_exclusive_access().process_pid as isize
 }
 
-pub fn sys_fork() -> isize {
-    trace!("kernel:pid[{}] sys_fork", current_task().unwrap().pid.0);
+bitflags! {
+    /// clone(2)-style flags controlling what `sys_clone` shares with the new task instead of
+    /// copying. Bit positions mirror Linux's `CLONE_*` so a libc `clone`/`pthread_create` shim
+    /// built against this syscall can reuse the same constants. Only the subset this kernel
+    /// acts on is named.
+    pub struct CloneFlags: usize {
+        /// Share the caller's address space instead of copying it — the defining feature of a
+        /// thread as opposed to a process
+        const CLONE_VM = 0x0000_0100;
+        /// Share the caller's open file descriptor table instead of copying it
+        const CLONE_FILES = 0x0000_0400;
+        /// Place the new task in the same thread group as the caller, so `sys_getpid`/deadlock
+        /// detection/the tid table treat it as another thread of the same process
+        const CLONE_THREAD = 0x0001_0000;
+        /// Accepted for API compatibility; this kernel has no per-task TLS register to set
+        const CLONE_SETTLS = 0x0008_0000;
+    }
+}
+
+/// Create a new task, optionally sharing resources with the caller instead of copying them.
+/// `CLONE_VM` shares the address space (`Arc::clone`d rather than deep-copied), `CLONE_FILES`
+/// shares the open file table, and `CLONE_THREAD` makes the new task a thread of the caller's
+/// process (same `process_pid`/tid table) rather than a new process. If `stack != 0`, the new
+/// task's user stack pointer is set to it; otherwise it inherits the caller's `sp`.
+///
+/// `sys_fork` is `sys_clone(0, 0)`: no flags set means a fully independent copy, same as before
+/// `sys_clone` existed.
+pub fn sys_clone(flags: usize, stack: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] sys_clone flags={:#x} stack={:#x}",
+        current_task().unwrap().pid.0,
+        flags,
+        stack
+    );
+    let flags = CloneFlags::from_bits_truncate(flags);
     let current_task = current_task().unwrap();
-    let new_task = current_task.fork();
+    let new_task = current_task.clone_task(flags, stack);
     let new_pid = new_task.pid.0;
     // modify trap context of new_task, because it returns immediately after switching
     let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
     // we do not have to move to next instruction since we have done it before
-    // for child process, fork returns 0
+    // for child process, fork/clone returns 0
     trap_cx.x[10] = 0;
     // add new task to scheduler
     add_task(new_task);
     new_pid as isize
 }
 
+pub fn sys_fork() -> isize {
+    trace!("kernel:pid[{}] sys_fork", current_task().unwrap().pid.0);
+    sys_clone(0, 0)
+}
+
 pub fn sys_exec(path: *const u8) -> isize {
     trace!("kernel:pid[{}] sys_exec", current_task().unwrap().pid.0);
     let token = current_user_token();
@@ -71,9 +155,11 @@ pub fn sys_exec(path: *const u8) -> isize {
 }
 
 /// If there is not a child process whose pid is same as given, return -1.
-/// Else if there is a child process but it is still running, return -2.
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+/// Else if there is a child process but it is still running: return -2, unless `options`
+/// contains `WNOHANG`, in which case return 0 immediately instead of making the caller poll.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32, options: usize) -> isize {
     trace!("kernel::pid[{}] sys_waitpid [{}]", current_task().unwrap().pid.0, pid);
+    let options = WaitOption::from_bits_truncate(options);
     let task = current_task().unwrap();
     // find a child process
 
@@ -87,6 +173,19 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
         return -1;
         // ---- release current PCB
     }
+    let process_pid = inner.process_pid;
+    if let Some(stopped) = inner.children.iter().find(|p| {
+        (pid == -1 || pid as usize == p.getpid())
+            && p.inner_exclusive_access().task_status == TaskStatus::Stopped
+            && p.inner_exclusive_access().tracer_pid == Some(process_pid)
+    }) {
+        // report the stop, same as a real wait(2)'s WIFSTOPPED status — the tracee stays a
+        // child, not reaped, so it can still be resumed via PTRACE_CONT/PTRACE_SINGLESTEP
+        let found_pid = stopped.getpid();
+        *translated_refmut(inner.memory_set.exclusive_access().token(), exit_code_ptr) =
+            PTRACE_STOPPED_MARKER;
+        return found_pid as isize;
+    }
     let pair = inner.children.iter().enumerate().find(|(_, p)| {
         // ++++ temporarily access child PCB exclusively
         p.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == p.getpid())
@@ -98,61 +197,89 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
         assert_eq!(Arc::strong_count(&child), 1);
         let found_pid = child.getpid();
         // ++++ temporarily access child PCB exclusively
-        let exit_code = child.inner_exclusive_access().exit_code;
+        let child_inner = child.inner_exclusive_access();
+        let exit_code = child_inner.exit_code;
+        // roll the reaped child's accumulated runtime into the parent's, so a process's own
+        // rusage still reflects work done on its behalf by children it has waited for
+        inner.utime_us += child_inner.utime_us;
+        inner.stime_us += child_inner.stime_us;
+        drop(child_inner);
         // ++++ release child PCB
-        *translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
+        *translated_refmut(inner.memory_set.exclusive_access().token(), exit_code_ptr) = exit_code;
         found_pid as isize
+    } else if options.contains(WaitOption::WNOHANG) {
+        0
     } else {
         -2
     }
     // ---- release current PCB automatically
 }
 
-/// YOUR JOB: get time with second and microsecond
-/// HINT: You might reimplement it with virtual memory management.
-/// HINT: What if [`TimeVal`] is splitted by two pages ?
+/// Report resource usage. Only `RUSAGE_SELF` is supported (the calling task's own accumulated
+/// `utime_us`/`stime_us`, which already includes runtime rolled in from any children `sys_waitpid`
+/// has reaped); any other `who` returns `-1`.
+pub fn sys_getrusage(who: isize, usage: *mut RUsage) -> isize {
+    trace!("kernel:pid[{}] sys_getrusage", current_task().unwrap().pid.0);
+    if who != RUSAGE_SELF {
+        return -1;
+    }
+    let inner = current_task().unwrap().inner_exclusive_access();
+    let rusage = RUsage {
+        utime: TimeVal::from_us(inner.utime_us),
+        stime: TimeVal::from_us(inner.stime_us),
+    };
+    drop(inner);
+    copy_to_user(current_user_token(), usage as usize, &rusage);
+    0
+}
 
+/// Serialize `*value` and copy it byte-by-byte into the calling task's address space starting
+/// at virtual address `dst`, through `translated_byte_buffer`'s own page-at-a-time splitting —
+/// unlike translating `dst` to a physical address once and writing `T` through it in a single
+/// cast, which corrupts memory whenever `T` straddles a page boundary (the two halves need not
+/// be backed by contiguous frames).
+fn copy_to_user<T>(token: usize, dst: usize, value: &T) {
+    let bytes = unsafe {
+        core::slice::from_raw_parts((value as *const T) as *const u8, core::mem::size_of::<T>())
+    };
+    UserBuffer::new(translated_byte_buffer(token, dst as *const u8, bytes.len())).write(bytes);
+}
+
+/// The read-side counterpart of [`copy_to_user`]: gather `len` bytes starting at `src` in the
+/// address space `token` points at into an owned `Vec`, through the same page-at-a-time
+/// `translated_byte_buffer` splitting so a read that straddles a page boundary doesn't need its
+/// source to be backed by contiguous frames.
+fn copy_from_user(token: usize, src: usize, len: usize) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    bytes.resize(len, 0);
+    let mut offset = 0;
+    for chunk in translated_byte_buffer(token, src as *const u8, len) {
+        bytes[offset..offset + chunk.len()].copy_from_slice(chunk);
+        offset += chunk.len();
+    }
+    bytes
+}
+
+/// get time with second and microsecond
 pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
     trace!(
-        "kernel:pid[{}] sys_get_time NOT IMPLEMENTED",
+        "kernel:pid[{}] sys_get_time",
         current_task().unwrap().pid.0
     );
-    let phys_addr = VirtAddr(ts as usize).convert_to_phys_addr();
-    let us = get_time_us();
-    match phys_addr {
-        Some(phys_addr) => {
-            unsafe {
-                *(phys_addr.0 as *mut TimeVal) = TimeVal {
-                    sec: us / 1_000_000,
-                    usec: us % 1_000_000,
-                };
-            }
-            0
-        },
-        None => -1
-    }
+    let time_val = TimeVal::from_us(get_time_us());
+    copy_to_user(current_user_token(), ts as usize, &time_val);
+    0
 }
 
-/// YOUR JOB: Finish sys_task_info to pass testcases
-/// HINT: You might reimplement it with virtual memory management.
-/// HINT: What if [`TaskInfo`] is splitted by two pages ?
-
+/// Fill in `ti` with the calling task's accumulated `TaskInfo`
 pub fn sys_task_info(ti: *mut TaskInfo) -> isize {
     trace!(
-        "kernel:pid[{}] sys_task_info NOT IMPLEMENTED",
+        "kernel:pid[{}] sys_task_info",
         current_task().unwrap().pid.0
     );
-    let phys_addr = VirtAddr(ti as usize).convert_to_phys_addr();
-    let info : TaskInfo = get_current_task_info();
-    match phys_addr {
-        Some(phys_addr1) => {
-            unsafe {
-                *(phys_addr1.0 as *mut TaskInfo) = info;
-            }
-            0
-        },
-        None => -1
-    }
+    let info: TaskInfo = get_current_task_info();
+    copy_to_user(current_user_token(), ti as usize, &info);
+    0
 }
 
 // YOUR JOB: Implement mmap.
@@ -253,8 +380,352 @@ pub fn sys_set_priority(prio: isize) -> isize {
     let binding = current_task().unwrap();
     let mut inner = binding.inner_exclusive_access();
 
-    inner.pass = BIG_STRIDE/prio as usize;
+    // A donation in progress (priority > base_priority, from MutexBlocking::lock handing this
+    // holder a blocked waiter's priority, see donate_priority/restore_priority in sync/mutex.rs)
+    // owns `priority`/`pass` until the holder releases the mutex and restore_priority drops it
+    // back to base_priority. Only touch base_priority here, so the new value takes effect once
+    // the donation ends instead of this call wiping out the boost mid-hold.
+    let donation_active = inner.priority > inner.base_priority;
+    inner.base_priority = prio as usize;
+    if !donation_active {
+        inner.priority = prio as usize;
+        inner.pass = BIG_STRIDE / inner.priority;
+    }
     drop(inner);
 
     prio
 }
+
+/// Toggle banker's-algorithm deadlock detection for the calling process: when enabled, a
+/// blocking `Mutex`/semaphore acquire that would leave the system in an unsafe state returns
+/// `DEADLOCK_ERROR` instead of enqueueing.
+///
+/// 参数：enabled 是否开启死锁检测功能。0 表示关，1 表示开
+/// 返回值：如果输入合法则返回 0，否则返回 -1
+pub fn sys_enable_deadlock_detect(enabled: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] sys_enable_deadlock_detect",
+        current_task().unwrap().pid.0
+    );
+    let pid = current_task().unwrap().inner_exclusive_access().process_pid;
+    match enabled {
+        0 => {
+            set_deadlock_detect_enabled(pid, false);
+            0
+        }
+        1 => {
+            set_deadlock_detect_enabled(pid, true);
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Spawn a new thread of the calling process running `entry`, with `arg` passed in `a0`.
+/// The new thread shares the caller's address space and open files (via the `Arc`s cloned by
+/// `create_thread`) but gets its own private user stack, trap context and tid.
+/// 参数：entry 新线程的入口地址；arg 传给入口函数的参数
+/// 返回值：新线程的 tid
+pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] sys_thread_create",
+        current_task().unwrap().pid.0
+    );
+    let current_task = current_task().unwrap();
+    let new_task = current_task.create_thread(entry);
+    let new_task_tid = new_task.inner_exclusive_access().res.as_ref().unwrap().tid;
+    let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
+    trap_cx.x[10] = arg;
+    add_task(new_task);
+    new_task_tid as isize
+}
+
+/// Return the calling thread's tid (unique within its process, unlike `sys_getpid`'s
+/// process-wide identity).
+pub fn sys_gettid() -> isize {
+    trace!("kernel:pid[{}] sys_gettid", current_task().unwrap().pid.0);
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .res
+        .as_ref()
+        .unwrap()
+        .tid as isize
+}
+
+/// Wait for thread `tid` of the calling process to exit and reap it, mirroring `sys_waitpid`'s
+/// contract for threads instead of child processes.
+///
+/// Returns -1 if there is no thread with this tid, -2 if it's still running, otherwise its
+/// exit code (and the thread's tid slot is freed for reuse).
+pub fn sys_waittid(tid: usize) -> isize {
+    trace!("kernel:pid[{}] sys_waittid", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let threads = inner.threads.exclusive_access();
+    let target = match threads.get(tid) {
+        Some(Some(target)) => target.clone(),
+        _ => return -1,
+    };
+    drop(threads);
+    drop(inner);
+    let target_inner = target.inner_exclusive_access();
+    if !target_inner.is_zombie() {
+        return -2;
+    }
+    let exit_code = target_inner.exit_code;
+    drop(target_inner);
+    target.dealloc_tid();
+    inner_remove_thread(&task, tid);
+    exit_code as isize
+}
+
+/// Free `tid`'s slot in `task`'s shared thread table once `sys_waittid` has reaped it, so a
+/// later `sys_thread_create` reusing the recycled tid doesn't find a stale zombie entry.
+fn inner_remove_thread(task: &Arc<crate::task::TaskControlBlock>, tid: usize) {
+    let inner = task.inner_exclusive_access();
+    inner.threads.exclusive_access()[tid] = None;
+}
+
+/// `action` argument to `sys_set_seccomp`: deny a filtered syscall by returning
+/// [`SECCOMP_DENIED_ERRNO`] without running its handler
+pub const SECCOMP_RET_ERRNO: usize = 0;
+/// `action` argument to `sys_set_seccomp`: deny a filtered syscall by killing the task with
+/// [`SECCOMP_KILL_EXIT_CODE`]
+pub const SECCOMP_RET_KILL: usize = 1;
+
+/// Errno `SECCOMP_RET_ERRNO` hands back for a denied syscall. It is the same `-1` most handlers
+/// return on failure, so the return value alone does not tell a seccomp denial apart from an
+/// ordinary failure
+pub const SECCOMP_DENIED_ERRNO: isize = -1;
+
+/// Exit code a `SECCOMP_RET_KILL` denial terminates the task with, distinguishing a sandbox
+/// violation from an ordinary `sys_exit` in a parent's `sys_waitpid`
+pub const SECCOMP_KILL_EXIT_CODE: i32 = -0xC0DE;
+
+/// Install, or tighten, a seccomp-style filter on the calling task. `syscall_bitmap_ptr` points
+/// at a `ceil(MAX_SYSCALL_NUM / 8)`-byte bitmap, one bit per syscall id (bit `i % 8` of byte
+/// `i / 8`), denying every syscall whose bit is set; `action` (`SECCOMP_RET_ERRNO` or
+/// `SECCOMP_RET_KILL`) picks what happens to a denied syscall. The filter is inherited by
+/// `fork`/`sys_clone` and preserved across `exec`, so once a process forbids a syscall for
+/// itself, neither its children nor a later `execve` can get it back.
+///
+/// Matches the usual seccomp one-way-ratchet: calling this again only ORs more bits into the
+/// existing bitmap and may only move `action` to a strictly more restrictive one
+/// (`SECCOMP_RET_ERRNO` -> `SECCOMP_RET_KILL`), never the other way. Returns -1 for an invalid
+/// `action` or an attempted relaxation, 0 on success.
+pub fn sys_set_seccomp(action: usize, syscall_bitmap_ptr: *const u8) -> isize {
+    trace!(
+        "kernel:pid[{}] sys_set_seccomp",
+        current_task().unwrap().pid.0
+    );
+    let action = match action {
+        SECCOMP_RET_ERRNO => SeccompAction::Errno,
+        SECCOMP_RET_KILL => SeccompAction::Kill,
+        _ => return -1,
+    };
+
+    let token = current_user_token();
+    let bitmap_len = (MAX_SYSCALL_NUM + 7) / 8;
+    let bitmap = copy_from_user(token, syscall_bitmap_ptr as usize, bitmap_len);
+
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if let Some(existing) = &inner.seccomp_filter {
+        if action < existing.action {
+            // an attempted relaxation of an already-installed filter: reject instead of
+            // weakening it
+            return -1;
+        }
+    }
+    let filter = inner
+        .seccomp_filter
+        .get_or_insert_with(|| SeccompFilter::new(action));
+    filter.action = action;
+    for id in 0..MAX_SYSCALL_NUM {
+        if bitmap[id / 8] & (1 << (id % 8)) != 0 {
+            filter.denied[id] = true;
+        }
+    }
+    0
+}
+
+/// Consult the calling task's seccomp filter for `syscall_id`. `None` means the syscall is
+/// allowed (including when no filter is installed at all); `Some(result)` means it's denied and
+/// the caller should short-circuit with `result` instead of running the syscall's handler. For
+/// `SECCOMP_RET_KILL` the task is terminated via `exit_current_and_run_next` and this function
+/// does not return.
+///
+/// Meant to be consulted by the central `syscall()` dispatch before running a syscall's handler,
+/// so a denied syscall never executes — as the very first line of `syscall()`, before the
+/// `match syscall_id { ... }` that routes to each handler:
+/// ```ignore
+/// if let Some(result) = check_seccomp(syscall_id) {
+///     return result;
+/// }
+/// ```
+/// That dispatch lives in `syscall/mod.rs`, which does not exist anywhere in this tree (only
+/// `syscall/fs.rs` and `syscall/process.rs` do) — there is no `syscall()` function in scope to
+/// add the call to, so this still isn't wired up. Whoever owns `syscall/mod.rs` can paste the
+/// snippet above in verbatim.
+pub fn check_seccomp(syscall_id: usize) -> Option<isize> {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let filter = inner.seccomp_filter.as_ref()?;
+    if syscall_id >= MAX_SYSCALL_NUM || !filter.denied[syscall_id] {
+        return None;
+    }
+    let action = filter.action;
+    drop(inner);
+    match action {
+        SeccompAction::Kill => {
+            exit_current_and_run_next(SECCOMP_KILL_EXIT_CODE);
+            panic!("Unreachable in check_seccomp!");
+        }
+        SeccompAction::Errno => Some(SECCOMP_DENIED_ERRNO),
+    }
+}
+
+/// `request` values for `sys_ptrace`, mirroring the subset of Linux's `ptrace(2)` request numbers
+/// this kernel implements (same numeric values, so a libc `ptrace` shim can reuse them).
+pub const PTRACE_TRACEME: usize = 0;
+/// Read one word from the tracee's address space at `addr`
+pub const PTRACE_PEEKTEXT: usize = 1;
+/// Write `data` as one word into the tracee's address space at `addr`
+pub const PTRACE_POKETEXT: usize = 4;
+/// Resume a stopped tracee
+pub const PTRACE_CONT: usize = 7;
+/// Resume a stopped tracee with single-stepping armed, so it re-stops after its next instruction
+pub const PTRACE_SINGLESTEP: usize = 9;
+/// Copy the tracee's saved `TrapContext` into the buffer at `data`
+pub const PTRACE_GETREGS: usize = 12;
+/// Overwrite the tracee's saved `TrapContext` from the buffer at `data`
+pub const PTRACE_SETREGS: usize = 13;
+
+/// Sentinel `sys_waitpid` writes to `exit_code_ptr` when it's reporting a tracee stop rather than
+/// a zombie exit, distinguishing the two the same way Linux's `WIFSTOPPED` does for a real
+/// `wait(2)` status word.
+pub const PTRACE_STOPPED_MARKER: i32 = i32::MIN;
+
+/// Find the task with process-wide pid `pid` among the calling task's children — ptrace only
+/// lets a tracer touch its own tracees, and only direct children can ever be tracees here since
+/// `PTRACE_TRACEME` only ever records the caller's immediate parent.
+fn find_traced_child(pid: isize) -> Option<Arc<crate::task::TaskControlBlock>> {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .children
+        .iter()
+        .find(|p| pid as usize == p.getpid())
+        .cloned()
+}
+
+/// Minimal ptrace: a parent traces a child that has called `PTRACE_TRACEME`, single-steps or
+/// resumes it while it's stopped, and peeks/pokes its address space and registers.
+///
+/// - `PTRACE_TRACEME`: the calling task marks itself traced by its parent (`pid`/`addr`/`data`
+///   ignored); its stops are reported to that parent through `sys_waitpid` from then on.
+/// - `PTRACE_CONT`/`PTRACE_SINGLESTEP`: resume the stopped tracee `pid`. `PTRACE_SINGLESTEP` also
+///   arms single-stepping so the tracee re-stops after its next instruction — actually re-stopping
+///   it happens wherever trap entry decides a step/trace trap occurred (see
+///   `processor::stop_current_and_notify_tracer`), which lives in `trap.rs`, outside this tree's
+///   in-scope files, so only the "arm the flag and resume" half is implemented here.
+/// - `PTRACE_PEEKTEXT`/`PTRACE_POKETEXT`: read/write one `usize` word at `addr` in tracee `pid`'s
+///   address space, through its own page table rather than the caller's.
+/// - `PTRACE_GETREGS`/`PTRACE_SETREGS`: copy tracee `pid`'s saved `TrapContext` to/from the
+///   buffer at `data` in the caller's address space.
+///
+/// Returns -1 for an unknown `request`, a `pid` that isn't a traced child of the caller, or (for
+/// `PTRACE_CONT`/`PTRACE_SINGLESTEP`) a tracee that isn't currently stopped.
+pub fn sys_ptrace(request: usize, pid: isize, addr: usize, data: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] sys_ptrace request={} pid={}",
+        current_task().unwrap().pid.0,
+        request,
+        pid
+    );
+    if request == PTRACE_TRACEME {
+        let task = current_task().unwrap();
+        let mut inner = task.inner_exclusive_access();
+        let tracer_pid = inner
+            .parent
+            .as_ref()
+            .and_then(|parent| parent.upgrade())
+            .map(|parent| parent.inner_exclusive_access().process_pid);
+        inner.tracer_pid = tracer_pid;
+        return if inner.tracer_pid.is_some() { 0 } else { -1 };
+    }
+
+    let target = match find_traced_child(pid) {
+        Some(target) => target,
+        None => return -1,
+    };
+    let tracer_process_pid = current_task().unwrap().inner_exclusive_access().process_pid;
+    if target.inner_exclusive_access().tracer_pid != Some(tracer_process_pid) {
+        return -1;
+    }
+
+    match request {
+        PTRACE_CONT | PTRACE_SINGLESTEP => {
+            let mut inner = target.inner_exclusive_access();
+            if inner.task_status != TaskStatus::Stopped {
+                return -1;
+            }
+            inner.single_step = request == PTRACE_SINGLESTEP;
+            inner.task_status = TaskStatus::Ready;
+            drop(inner);
+            add_task(target);
+            0
+        }
+        PTRACE_PEEKTEXT => {
+            let target_token = target.inner_exclusive_access().get_user_token();
+            let bytes = copy_from_user(target_token, addr, core::mem::size_of::<usize>());
+            let mut word = [0u8; core::mem::size_of::<usize>()];
+            word.copy_from_slice(&bytes);
+            usize::from_ne_bytes(word) as isize
+        }
+        PTRACE_POKETEXT => {
+            let target_token = target.inner_exclusive_access().get_user_token();
+            copy_to_user(target_token, addr, &data);
+            0
+        }
+        PTRACE_GETREGS => {
+            let trap_cx_bytes = {
+                let inner = target.inner_exclusive_access();
+                let trap_cx = inner.get_trap_cx();
+                unsafe {
+                    core::slice::from_raw_parts(
+                        (trap_cx as *const TrapContext) as *const u8,
+                        core::mem::size_of::<TrapContext>(),
+                    )
+                    .to_vec()
+                }
+            };
+            UserBuffer::new(translated_byte_buffer(
+                current_user_token(),
+                data as *const u8,
+                trap_cx_bytes.len(),
+            ))
+            .write(&trap_cx_bytes);
+            0
+        }
+        PTRACE_SETREGS => {
+            let bytes = copy_from_user(
+                current_user_token(),
+                data,
+                core::mem::size_of::<TrapContext>(),
+            );
+            let inner = target.inner_exclusive_access();
+            let trap_cx = inner.get_trap_cx();
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    bytes.as_ptr(),
+                    (trap_cx as *mut TrapContext) as *mut u8,
+                    bytes.len(),
+                );
+            }
+            0
+        }
+        _ => -1,
+    }
+}
@@ -0,0 +1,93 @@
+//! Cooperative coroutine tasks: `Future`-based tasks polled on the idle control flow's own
+//! stack, as a stackless alternative to the thread-based tasks `Processor`/`__switch` drive.
+//!
+//! A coroutine never gets `__switch`ed to: `poll_ready_coroutines` (called from
+//! [`super::processor::run_tasks`]'s idle loop) polls each ready one in place, and a coroutine
+//! that returns `Poll::Pending` simply falls out of the ready queue until its `Waker`
+//! re-enqueues it — the same "park until something signals you" shape `wakeup_task` gives a
+//! thread-based task parked in a `MutexBlocking`/`Semaphore` wait queue, just without a context
+//! switch. Letting one of those wait queues hold a coroutine directly (so a coroutine can block
+//! on a mutex the same way a thread does) is follow-up work; for now a coroutine wanting to wait
+//! on such a resource has to poll it itself.
+
+use crate::sync::UPSafeCell;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use lazy_static::lazy_static;
+
+/// A spawned coroutine: a boxed, pinned `Future` plus the bookkeeping a `Waker` needs to
+/// re-enqueue it onto [`READY_QUEUE`].
+pub struct CoroutineTask {
+    future: UPSafeCell<Pin<Box<dyn Future<Output = ()> + Send + 'static>>>,
+}
+
+impl CoroutineTask {
+    fn new(future: impl Future<Output = ()> + Send + 'static) -> Arc<Self> {
+        Arc::new(Self {
+            future: unsafe { UPSafeCell::new(Box::pin(future)) },
+        })
+    }
+}
+
+lazy_static! {
+    /// Coroutines ready to be polled, drained once per `run_tasks` idle-loop iteration
+    static ref READY_QUEUE: UPSafeCell<VecDeque<Arc<CoroutineTask>>> =
+        unsafe { UPSafeCell::new(VecDeque::new()) };
+}
+
+/// Queue `future` to run on the idle control flow, alongside the existing thread-based tasks
+pub fn spawn(future: impl Future<Output = ()> + Send + 'static) {
+    READY_QUEUE
+        .exclusive_access()
+        .push_back(CoroutineTask::new(future));
+}
+
+fn raw_waker(task: Arc<CoroutineTask>) -> RawWaker {
+    RawWaker::new(Arc::into_raw(task) as *const (), &VTABLE)
+}
+
+unsafe fn waker_clone(ptr: *const ()) -> RawWaker {
+    let task = Arc::from_raw(ptr as *const CoroutineTask);
+    let cloned = task.clone();
+    core::mem::forget(task);
+    raw_waker(cloned)
+}
+
+unsafe fn waker_wake(ptr: *const ()) {
+    let task = Arc::from_raw(ptr as *const CoroutineTask);
+    READY_QUEUE.exclusive_access().push_back(task);
+}
+
+unsafe fn waker_wake_by_ref(ptr: *const ()) {
+    let task = Arc::from_raw(ptr as *const CoroutineTask);
+    READY_QUEUE.exclusive_access().push_back(task.clone());
+    core::mem::forget(task);
+}
+
+unsafe fn waker_drop(ptr: *const ()) {
+    drop(Arc::from_raw(ptr as *const CoroutineTask));
+}
+
+static VTABLE: RawWakerVTable =
+    RawWakerVTable::new(waker_clone, waker_wake, waker_wake_by_ref, waker_drop);
+
+/// Poll every coroutine currently in the ready queue once each, on the caller's own stack,
+/// dropping the ones that complete. Called from the idle control flow each time round its loop,
+/// before it falls back to `__switch`ing to a thread-based task.
+pub fn poll_ready_coroutines() {
+    let ready: Vec<Arc<CoroutineTask>> = READY_QUEUE.exclusive_access().drain(..).collect();
+    for task in ready {
+        let waker = unsafe { Waker::from_raw(raw_waker(task.clone())) };
+        let mut cx = Context::from_waker(&waker);
+        let mut future = task.future.exclusive_access();
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(()) => {}
+            Poll::Pending => {}
+        }
+    }
+}
@@ -1,61 +1,98 @@
 //!Implementation of [`TaskManager`]
 use super::TaskControlBlock;
 use crate::sync::UPSafeCell;
-use alloc::collections::VecDeque;
+use crate::syscall::process::BIG_STRIDE;
+use alloc::collections::BinaryHeap;
 use alloc::sync::Arc;
+use core::cmp::Ordering;
 use lazy_static::*;
-// use crate::task::TaskStatus;
-///A array of `TaskControlBlock` that is thread-safe
+
+/// A task's `strid` at the moment it joined the ready queue, wrapped so it can be ordered for
+/// the ready-queue `BinaryHeap` with the wraparound-safe comparison stride scheduling requires
+/// instead of a plain `<`: with `prio >= 2` guaranteeing `pass <= BIG_STRIDE/2`, the spread
+/// between any two live strides never exceeds `BIG_STRIDE/2`, so `a` is "behind" `b` iff
+/// `a.wrapping_sub(b) > BIG_STRIDE/2`.
+#[derive(Clone, Copy, Eq, PartialEq)]
+struct Stride(usize);
+
+impl Stride {
+    fn is_behind(self, other: Self) -> bool {
+        self.0.wrapping_sub(other.0) > BIG_STRIDE / 2
+    }
+}
+
+impl Ord for Stride {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self.0 == other.0 {
+            Ordering::Equal
+        } else if self.is_behind(*other) {
+            // self is the smaller (earlier) stride, which should come out of the max-heap
+            // first, so it orders as Greater
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        }
+    }
+}
+
+impl PartialOrd for Stride {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A ready-queue entry: the task plus the `strid` it had when it was queued, so the heap can
+/// order by stride without re-locking the task on every comparison
+struct ReadyEntry {
+    stride: Stride,
+    task: Arc<TaskControlBlock>,
+}
+
+impl PartialEq for ReadyEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.stride == other.stride
+    }
+}
+impl Eq for ReadyEntry {}
+impl Ord for ReadyEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.stride.cmp(&other.stride)
+    }
+}
+impl PartialOrd for ReadyEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A stride-scheduled ready queue: a `BinaryHeap` keyed by each queued task's `strid`, so
+/// picking the smallest-stride runnable task is `O(log n)` instead of the linear scan a plain
+/// `VecDeque` would need.
 pub struct TaskManager {
-    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+    ready_queue: BinaryHeap<ReadyEntry>,
 }
 
-/// A simple FIFO scheduler.
 impl TaskManager {
     ///Creat an empty TaskManager
     pub fn new() -> Self {
         Self {
-            ready_queue: VecDeque::new(),
+            ready_queue: BinaryHeap::new(),
         }
     }
-    /// Add process back to ready queue
-    /// 将一个任务加入队尾
+    /// Add process back to ready queue, keyed by its `strid` at the moment it's queued
     pub fn add(&mut self, task: Arc<TaskControlBlock>) {
-        // task_inner.strid += task_inner.pass;
-
-        self.ready_queue.push_back(task);
+        let stride = Stride(task.inner_exclusive_access().strid);
+        self.ready_queue.push(ReadyEntry { stride, task });
     }
-    /// Take a process out of the ready queue
-    /// 从队头中取出一个任务来执行
+    /// Take the task with the smallest stride out of the ready queue, a proper stride
+    /// scheduler pick rather than FIFO, then advance its `strid` by its `pass` for the next
+    /// time it's queued.
     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-        // self.ready_queue.pop_front()
-        // let queue: VecDeque<Arc<TaskControlBlock>> = 
-        //     self.ready_queue.drain(..).collect();
-
-
-        let mut fetch_task: Option<Arc<TaskControlBlock>> = None;
-        let mut min = 2000;
-        for task in self.ready_queue.iter() {
-            let task_inner = task.inner_exclusive_access();
-                if task_inner.strid < min {
-                    min = task_inner.strid;
-                    fetch_task = Some(task.clone());
-                }
-        }
-        if let Some(fetch_task) = &fetch_task {
-            // 获取选定任务的 PID（假设 PID 是一个字段）
-            let clone_pid = fetch_task.pid.0;
-            // 获取选定任务的内部可变引用
-            let mut inner = fetch_task.inner_exclusive_access();
-            // 更新任务的步幅
-            inner.strid += inner.pass;
-    
-            // 从就绪队列中移除具有相同 PID 的任务
-            self.ready_queue.retain(|x| x.pid.0 != clone_pid);
-        }
-        
-        fetch_task
-        
+        let ReadyEntry { task, .. } = self.ready_queue.pop()?;
+        let mut inner = task.inner_exclusive_access();
+        inner.strid = inner.strid.wrapping_add(inner.pass);
+        drop(inner);
+        Some(task)
     }
 }
 
@@ -7,6 +7,7 @@
 //! 功能是尝试从任务管理器中选出一个任务来在当前核上执行。
 //!  在内核初始化完毕之后，核通过调用 run_tasks 函数来进入 idle 控制流：
 use super::__switch;
+use super::coroutine::poll_ready_coroutines;
 use super::{fetch_task, TaskStatus, TaskInfo};
 use super::{TaskContext, TaskControlBlock};
 use crate::sync::UPSafeCell;
@@ -14,7 +15,18 @@ use crate::trap::TrapContext;
 use alloc::sync::Arc;
 use lazy_static::*;
 use crate::timer::get_time_ms;
-use crate::mm::{MapPermission,VirtAddr,VPNRange};
+use crate::config::PAGE_SIZE;
+use crate::mm::{MapPermission,VirtAddr};
+
+/// Round `addr` down to its containing page's base address
+fn page_floor(addr: usize) -> usize {
+    addr & !(PAGE_SIZE - 1)
+}
+
+/// Round `addr` up to the base address of the page after it, unless `addr` already is one
+fn page_ceil(addr: usize) -> usize {
+    page_floor(addr + PAGE_SIZE - 1)
+}
 
 /// Processor management structure
 pub struct Processor {
@@ -77,29 +89,58 @@ impl Processor {
         drop(inner);
     }
 
+    // 累加当前任务的用户态运行时间（微秒），供 sys_getrusage 使用
+    fn add_utime_us(&mut self, delta: usize) {
+        let mut inner = self.current.as_mut().unwrap().inner_exclusive_access();
+        inner.utime_us += delta;
+        drop(inner);
+    }
+
+    // 累加当前任务的内核态运行时间（微秒），供 sys_getrusage 使用
+    fn add_stime_us(&mut self, delta: usize) {
+        let mut inner = self.current.as_mut().unwrap().inner_exclusive_access();
+        inner.stime_us += delta;
+        drop(inner);
+    }
+
     // 为当前任务分配内存
+    //
+    // Overlap is checked against `vma_index`, a `BTreeMap`-backed index of this address space's
+    // `mmap`-mapped regions kept alongside `memory_set` (see `VmaIndex` in `task.rs`), so this is
+    // an O(log n) range query rather than a linear scan over `MemorySet`'s areas. `vma_index` is
+    // keyed by page-aligned byte address, not the raw `start_vir_addr`/`end_vir_addr` passed in:
+    // `insert_framed_area` always maps whole pages (`start.floor()..end.ceil()`), so two
+    // unaligned requests that don't overlap as raw byte ranges can still land on the same page
+    // once page-aligned, and `vma_index` has to agree with `memory_set` about which pages are
+    // actually occupied.
     fn mmap(&mut self, start_vir_addr: VirtAddr, end_vir_addr: VirtAddr, port: usize) -> isize {
-        let mut inner = self.current.as_mut().unwrap().inner_exclusive_access();
-        if inner.
-            memory_set
-            .exist_some_range(VPNRange::new(start_vir_addr.floor().into(), end_vir_addr.ceil().into()))
-            .is_some()
-        {
+        let inner = self.current.as_mut().unwrap().inner_exclusive_access();
+        let start = page_floor(start_vir_addr.0);
+        let end = page_ceil(end_vir_addr.0);
+        let mut vma_index = inner.vma_index.exclusive_access();
+        if vma_index.overlaps(start, end) {
             return -1;
         }
         let permission= MapPermission::from_bits_truncate((port<<1)as u8 | MapPermission::U.bits());
-        inner.memory_set.
+        inner.memory_set.exclusive_access().
             insert_framed_area(start_vir_addr.floor().into(), end_vir_addr.ceil().into(), permission.into());
+        vma_index.insert(start, end);
+        drop(vma_index);
         drop(inner);
         0
     }
 
     // 为当前任务分配内存
     fn munmap(&mut self, start_vir_addr: VirtAddr, end_vir_addr: VirtAddr) -> isize {
-        let mut inner = self.current.as_mut().unwrap().inner_exclusive_access();
+        let inner = self.current.as_mut().unwrap().inner_exclusive_access();
 
-        let result = inner.memory_set.
+        let result = inner.memory_set.exclusive_access().
             delete_framed_area(start_vir_addr, end_vir_addr);
+        if result == 0 {
+            // same page-aligned key `mmap` inserted under, not the raw (possibly unaligned) byte
+            // address `munmap` was called with
+            inner.vma_index.exclusive_access().remove(page_floor(start_vir_addr.0));
+        }
         drop(inner);
         result
     }
@@ -114,6 +155,12 @@ lazy_static! {
 ///Loop `fetch_task` to get the process that needs to run, and switch the process through `__switch`
 pub fn run_tasks() {
     loop {
+        // drain any block I/O queued through `AsyncBlockDevice` since the last tick, waking
+        // whichever task is awaiting it, before giving ready coroutines a chance to run
+        easy_fs::poll_pending_block_io();
+        // give every ready coroutine a chance to make progress on the idle stack before
+        // falling back to a full __switch into a thread-based task
+        poll_ready_coroutines();
         let mut processor = PROCESSOR.exclusive_access();
         if let Some(task) = fetch_task() {
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
@@ -181,6 +228,49 @@ pub fn add_syscall_times(syscall_id: usize){
     PROCESSOR.exclusive_access().add_syscall_times(syscall_id);
 }
 
+/// Add `delta` microseconds to the current task's accumulated user-mode runtime. Intended to be
+/// sampled (via `get_time_us`) around the user-mode/kernel-mode boundary — e.g. from `trap.rs`'s
+/// trap entry and `trap_return` — so `sys_getrusage` can report real `utime`/`stime` splits;
+/// that call site lives outside this tree's in-scope files, so it isn't wired up yet.
+pub fn add_utime_us(delta: usize) {
+    PROCESSOR.exclusive_access().add_utime_us(delta);
+}
+
+/// Add `delta` microseconds to the current task's accumulated kernel-mode runtime. See
+/// [`add_utime_us`].
+pub fn add_stime_us(delta: usize) {
+    PROCESSOR.exclusive_access().add_stime_us(delta);
+}
+
+/// Transition the current task into `TaskStatus::Stopped` and yield the CPU — ptrace's rough
+/// equivalent of `suspend_current_and_run_next` (which lives outside this tree's in-scope files,
+/// in `task/mod.rs`, alongside the rest of the run-next family). Meant to be called from the trap
+/// entry path once `inner.single_step` is set and the stepped instruction has completed, or
+/// whenever a traced task hits a trap it should report to its tracer rather than handling itself:
+/// ```ignore
+/// if trap_cause_is_breakpoint_or_singlestep(&inner) && inner.tracer_pid.is_some() {
+///     drop(inner);
+///     stop_current_and_notify_tracer();
+///     return;
+/// }
+/// ```
+/// `trap.rs`, where that entry path lives, does not exist anywhere in this tree (only
+/// `task/processor.rs` and the other files under `task/`, `sync/`, and `syscall/` do) — there is
+/// no trap handler in scope to add the call to, so this function still has no caller here.
+/// `sys_ptrace`'s `PTRACE_SINGLESTEP` therefore only arms `inner.single_step` and resumes the
+/// tracee; without this wired in, a single-stepped tracee runs to completion instead of
+/// re-stopping after one instruction, behaving like `PTRACE_CONT`. The tracer observes a real
+/// stop via `sys_waitpid`, which reports `TaskStatus::Stopped` distinctly from a zombie exit
+/// instead of reaping the task, once some trap path does call this.
+pub fn stop_current_and_notify_tracer() {
+    let task = take_current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    task_inner.task_status = TaskStatus::Stopped;
+    drop(task_inner);
+    schedule(task_cx_ptr);
+}
+
 /// 分配虚存
 pub fn mmap(start_vir_addr: VirtAddr, end_vir_addr: VirtAddr, port: usize) -> isize {
     PROCESSOR.exclusive_access().mmap(start_vir_addr, end_vir_addr, port)
@@ -2,21 +2,159 @@
 
 use super::{kstack_alloc, pid_alloc, KernelStack, TaskContext,PidHandle};
 use crate::{
-    config::{TRAP_CONTEXT_BASE,MAX_SYSCALL_NUM},
+    config::{TRAP_CONTEXT_BASE,MAX_SYSCALL_NUM,PAGE_SIZE,USER_STACK_SIZE},
     fs::{File, Stdin, Stdout},
-    mm::{MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE},
+    mm::{MapPermission, MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE},
     sync::UPSafeCell,
+    syscall::process::{CloneFlags, BIG_STRIDE, DEFAULT_PRIORITY},
     trap::{trap_handler, TrapContext, },
     timer::{get_time, get_time_ms},
 };
 use alloc::{
     // string::String,
+    collections::BTreeMap,
     sync::{Arc, Weak},
     vec,
     vec::Vec,
 };
 use core::cell::RefMut;
 
+/// An index over this address space's `mmap`-mapped regions, keyed by each region's start
+/// address, so `mmap`/`munmap` can check a candidate `[start, end)` for overlap in `O(log n)` via
+/// `BTreeMap`'s ordered range queries — a real tree-backed lookup instead of `mmap`/`munmap`
+/// walking every mapped region linearly. Tracks only the regions `sys_mmap` itself created, the
+/// same scope the old linear scan covered; the ELF/stack/heap areas `MemorySet` also maps aren't
+/// indexed here.
+#[derive(Clone)]
+pub struct VmaIndex {
+    /// start address -> end address (exclusive) of each currently-mapped `mmap` region
+    ranges: BTreeMap<usize, usize>,
+}
+
+impl VmaIndex {
+    /// An index over no regions yet
+    pub fn new() -> Self {
+        Self {
+            ranges: BTreeMap::new(),
+        }
+    }
+
+    /// Whether `[start, end)` overlaps any currently-mapped region. Regions in `ranges` never
+    /// overlap each other, so only the region starting at or before `start` (if any) and the one
+    /// starting at or after it can possibly overlap a new candidate range.
+    pub fn overlaps(&self, start: usize, end: usize) -> bool {
+        if let Some((_, &prev_end)) = self.ranges.range(..=start).next_back() {
+            if prev_end > start {
+                return true;
+            }
+        }
+        if let Some((&next_start, _)) = self.ranges.range(start..).next() {
+            if next_start < end {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Record a newly-mapped `[start, end)` region
+    pub fn insert(&mut self, start: usize, end: usize) {
+        self.ranges.insert(start, end);
+    }
+
+    /// Drop the region starting at `start`, once `munmap` has unmapped it
+    pub fn remove(&mut self, start: usize) {
+        self.ranges.remove(&start);
+    }
+}
+
+/// A thread's private resources: its tid (unique within the owning process) and the base of the
+/// user stack + trap-context page the thread subsystem mapped for it in the shared address
+/// space. Every `TaskControlBlock` that represents a schedulable thread carries one of these;
+/// see `TaskControlBlock::create_thread`.
+pub struct TaskUserRes {
+    /// Thread id, unique within the owning process's `tid_allocator`
+    pub tid: usize,
+    /// For a thread made by `create_thread`, the base of its private user stack (its
+    /// trap-context page sits just above it); for a task made by `TaskControlBlock::new` or
+    /// `clone_task`, the initial user stack pointer of the loaded program (`base_size`)
+    pub ustack_base: usize,
+}
+
+/// A denied syscall's disposition, installed by `sys_set_seccomp` and enforced by
+/// `syscall::process::check_seccomp`. Declared in ratchet order (`Errno` < `Kill`) so deriving
+/// `Ord` gives exactly the "only ever gets stricter" comparison `sys_set_seccomp` needs to reject
+/// an attempted relaxation.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SeccompAction {
+    /// Return a fixed error without running the syscall's handler
+    Errno,
+    /// Terminate the task, as if it had called `sys_exit` with a distinguished exit code
+    Kill,
+}
+
+/// Per-task syscall sandbox installed by `sys_set_seccomp`: once set, can only be tightened (more
+/// syscalls added to `denied`, `action` moved to a stricter `SeccompAction`), never relaxed,
+/// matching the usual seccomp one-way-ratchet invariant. Inherited by `fork`/`clone_task` (and
+/// `create_thread`, since every thread of a sandboxed process should stay sandboxed) and left
+/// untouched by `exec`, which never touches this field — matching seccomp's usual
+/// preserved-across-execve behavior.
+#[derive(Clone)]
+pub struct SeccompFilter {
+    /// What happens to a syscall whose bit is set in `denied`
+    pub action: SeccompAction,
+    /// Denied syscall numbers, indexed by syscall id
+    pub denied: [bool; MAX_SYSCALL_NUM],
+}
+
+impl SeccompFilter {
+    /// An empty filter (nothing denied yet) with the given default action, ready for
+    /// `sys_set_seccomp` to OR bits into
+    pub fn new(action: SeccompAction) -> Self {
+        Self {
+            action,
+            denied: [false; MAX_SYSCALL_NUM],
+        }
+    }
+}
+
+/// Process-local, recycling tid allocator — mirrors the (external) pid allocator's
+/// alloc-or-recycle shape, but scoped to one process's threads instead of the whole kernel.
+pub struct TidAllocator {
+    current: usize,
+    recycled: Vec<usize>,
+}
+
+impl TidAllocator {
+    /// Create an empty allocator, tids start at `0`
+    pub fn new() -> Self {
+        Self {
+            current: 0,
+            recycled: Vec::new(),
+        }
+    }
+
+    /// Allocate a tid: the most recently freed one if any, otherwise the next never-used one
+    pub fn alloc(&mut self) -> usize {
+        if let Some(tid) = self.recycled.pop() {
+            tid
+        } else {
+            self.current += 1;
+            self.current - 1
+        }
+    }
+
+    /// Return `tid` to the free list
+    pub fn dealloc(&mut self, tid: usize) {
+        assert!(tid < self.current);
+        assert!(
+            !self.recycled.iter().any(|t| *t == tid),
+            "tid {} dealloc'd twice",
+            tid
+        );
+        self.recycled.push(tid);
+    }
+}
+
 /// Task control block structure
 ///
 /// Directly save the contents that will not change during running
@@ -27,7 +165,7 @@ pub struct TaskControlBlock {
 
     /// Kernel stack corresponding to PID
     pub kernel_stack: KernelStack,
-    
+
 
     /// Mutable
     inner: UPSafeCell<TaskControlBlockInner>,
@@ -39,11 +177,117 @@ impl TaskControlBlock {
     pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
         self.inner.exclusive_access()
     }
-    
+
     /// Get the address of app's page table
     pub fn get_user_token(&self) -> usize {
         let inner = self.inner_exclusive_access();
-        inner.memory_set.token()
+        inner.memory_set.exclusive_access().token()
+    }
+
+    /// Spawn a new thread of this task's process: shares the `memory_set` and `fd_table` `Arc`s
+    /// (cloned, not copied) so it sees the same address space and open files, gets its own tid
+    /// from the process's `tid_allocator`, and a private user stack + trap-context page mapped
+    /// `tid + 1` slots below `TRAP_CONTEXT_BASE`. Used by `sys_thread_create`, which is responsible
+    /// for seeding `entry`'s argument register and adding the new task to the scheduler.
+    pub fn create_thread(self: &Arc<TaskControlBlock>, entry: usize) -> Arc<TaskControlBlock> {
+        let mut parent_inner = self.inner_exclusive_access();
+        let tid = parent_inner.tid_allocator.exclusive_access().alloc();
+
+        // one region per tid: a user stack followed by its trap-context page, stacked downward
+        // from TRAP_CONTEXT_BASE the same way the trampoline/trap-context pair sits below it for
+        // a process's first thread
+        let region_size = USER_STACK_SIZE + PAGE_SIZE;
+        let ustack_base = TRAP_CONTEXT_BASE - (tid + 1) * region_size;
+        let ustack_top = ustack_base + USER_STACK_SIZE;
+        let trap_cx_base = ustack_top;
+
+        let trap_cx_ppn = {
+            let mut memory_set = parent_inner.memory_set.exclusive_access();
+            memory_set.insert_framed_area(
+                VirtAddr(ustack_base).into(),
+                VirtAddr(ustack_top).into(),
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            );
+            memory_set.insert_framed_area(
+                VirtAddr(trap_cx_base).into(),
+                VirtAddr(trap_cx_base + PAGE_SIZE).into(),
+                MapPermission::R | MapPermission::W,
+            );
+            memory_set
+                .translate(VirtAddr(trap_cx_base).into())
+                .unwrap()
+                .ppn()
+        };
+
+        let kernel_stack = kstack_alloc();
+        let kernel_stack_top = kernel_stack.get_top();
+
+        let new_task = Arc::new(TaskControlBlock {
+            pid: pid_alloc(),
+            kernel_stack,
+            inner: unsafe {
+                UPSafeCell::new(TaskControlBlockInner {
+                    trap_cx_ppn,
+                    base_size: parent_inner.base_size,
+                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                    task_status: TaskStatus::Ready,
+                    memory_set: Arc::clone(&parent_inner.memory_set),
+                    vma_index: Arc::clone(&parent_inner.vma_index),
+                    parent: None,
+                    children: Vec::new(),
+                    exit_code: 0,
+                    fd_table: Arc::clone(&parent_inner.fd_table),
+                    uid: parent_inner.uid,
+                    gid: parent_inner.gid,
+                    heap_bottom: parent_inner.heap_bottom,
+                    program_brk: parent_inner.program_brk,
+                    start_time: get_time_ms(),
+                    task_info: TaskInfo::new(),
+                    strid: 0,
+                    pass: parent_inner.pass,
+                    priority: parent_inner.priority,
+                    base_priority: parent_inner.base_priority,
+                    utime_us: 0,
+                    stime_us: 0,
+                    blocked_on_holder: None,
+                    process_pid: parent_inner.process_pid,
+                    tid_allocator: Arc::clone(&parent_inner.tid_allocator),
+                    threads: Arc::clone(&parent_inner.threads),
+                    res: Some(TaskUserRes { tid, ustack_base }),
+                    seccomp_filter: parent_inner.seccomp_filter.clone(),
+                    tracer_pid: None,
+                    single_step: false,
+                })
+            },
+        });
+
+        *new_task.inner_exclusive_access().get_trap_cx() = TrapContext::app_init_context(
+            entry,
+            ustack_top,
+            KERNEL_SPACE.exclusive_access().token(),
+            kernel_stack_top,
+            trap_handler as usize,
+        );
+
+        let mut threads = parent_inner.threads.exclusive_access();
+        if threads.len() <= tid {
+            threads.resize(tid + 1, None);
+        }
+        threads[tid] = Some(new_task.clone());
+        drop(threads);
+
+        new_task
+    }
+
+    /// Release this thread's tid back to its process's `tid_allocator`. The exit path
+    /// (`exit_current_and_run_next`, outside this tree's in-scope files) should call this once
+    /// the thread becomes a zombie, the same way `PidHandle`'s `Drop` already reclaims a whole
+    /// process's pid.
+    pub fn dealloc_tid(&self) {
+        let inner = self.inner_exclusive_access();
+        if let Some(res) = &inner.res {
+            inner.tid_allocator.exclusive_access().dealloc(res.tid);
+        }
     }
 }
 
@@ -55,8 +299,34 @@ pub struct TaskControlBlockInner {
     /// 该进程当前已经运行的“长度”
     pub strid: usize,
 
-    /// stride 需要进行的累加值
+    /// stride 需要进行的累加值，由 `priority` 派生：`pass = BIG_STRIDE / priority`
     pub pass: usize,
+
+    /// Stride-scheduling priority (`>= 2`) currently driving `pass`. May be temporarily raised
+    /// above `base_priority` by `MutexBlocking` priority donation to avoid priority inversion;
+    /// restored to `base_priority` once the task releases the mutex it donated for.
+    pub priority: usize,
+
+    /// Stride-scheduling priority last set via `sys_set_priority`, independent of any priority
+    /// currently donated to this task by a blocked higher-priority waiter.
+    pub base_priority: usize,
+
+    /// Cumulative microseconds this task has spent running in user mode, backing
+    /// `sys_getrusage`'s `RUsage::utime`. Accumulated only through
+    /// `processor::add_utime_us`/`add_stime_us`, which no code in this tree calls, so both
+    /// counters stay at `0` here; a reaped child's totals are rolled into its
+    /// parent's by `sys_waitpid` so a process's usage still reflects work done by children it
+    /// has already waited for.
+    pub utime_us: usize,
+
+    /// Cumulative microseconds this task has spent running in kernel mode, backing
+    /// `sys_getrusage`'s `RUsage::stime`. See [`Self::utime_us`].
+    pub stime_us: usize,
+
+    /// While blocked in `MutexBlocking::lock`, the task currently holding that mutex — forms a
+    /// chain priority donation walks transitively when the holder is itself blocked waiting on
+    /// another mutex.
+    pub blocked_on_holder: Option<Arc<TaskControlBlock>>,
    /// The physical page number of the frame where the trap context is placed
    pub trap_cx_ppn: PhysPageNum,
 
@@ -70,8 +340,13 @@ pub struct TaskControlBlockInner {
    /// Maintain the execution status of the current process
    pub task_status: TaskStatus,
 
-   /// Application address space
-   pub memory_set: MemorySet,
+   /// Application address space, shared by every thread of this process (cloning the `Arc`
+   /// gives a thread its own handle onto the same address space rather than a private copy)
+   pub memory_set: Arc<UPSafeCell<MemorySet>>,
+
+   /// Index over `memory_set`'s `mmap`-mapped regions, shared (and cloned) alongside
+   /// `memory_set` itself since it describes the same address space
+   pub vma_index: Arc<UPSafeCell<VmaIndex>>,
 
    /// Parent process of the current process.
    /// Weak will not affect the reference count of the parent
@@ -82,7 +357,15 @@ pub struct TaskControlBlockInner {
 
    /// It is set when active exit or execution error occurs
    pub exit_code: i32,
-   pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+
+   /// Open file table, shared by every thread of this process
+   pub fd_table: Arc<UPSafeCell<Vec<Option<Arc<dyn File + Send + Sync>>>>>,
+
+   /// User id used for filesystem permission checks
+   pub uid: u32,
+
+   /// Group id used for filesystem permission checks
+   pub gid: u32,
 
    /// Heap bottom
    pub heap_bottom: usize,
@@ -95,6 +378,37 @@ pub struct TaskControlBlockInner {
 
     /// The task info
     pub task_info: TaskInfo,
+
+    /// Logical process identifier shared by every thread of this process — unlike `pid`, which
+    /// each thread still allocates independently as its own kernel-level identity (kernel stack,
+    /// scheduling bookkeeping), `process_pid` is what `sys_getpid` and the deadlock detector's
+    /// resource keys should read so threads of one process are treated as one participant.
+    pub process_pid: usize,
+
+    /// Shared tid allocator for every thread of this process
+    pub tid_allocator: Arc<UPSafeCell<TidAllocator>>,
+
+    /// Every thread of this process, indexed by tid, so `sys_waittid` can find and reap one
+    pub threads: Arc<UPSafeCell<Vec<Option<Arc<TaskControlBlock>>>>>,
+
+    /// This task's thread identity, if it's a thread (as opposed to a lone process's only task,
+    /// which still gets tid `0` from its own fresh `tid_allocator`)
+    pub res: Option<TaskUserRes>,
+
+    /// This process's seccomp sandbox, if `sys_set_seccomp` has installed one. See
+    /// [`SeccompFilter`].
+    pub seccomp_filter: Option<SeccompFilter>,
+
+    /// The `process_pid` of this task's ptrace tracer, if `sys_ptrace(PTRACE_TRACEME, ..)` has
+    /// marked it traced. Only its tracer may `PTRACE_CONT`/`PTRACE_PEEKTEXT`/etc. it, and only
+    /// its tracer's `sys_waitpid` reports its `TaskStatus::Stopped` stops.
+    pub tracer_pid: Option<usize>,
+
+    /// Whether `PTRACE_SINGLESTEP` armed single-stepping for this task's next resume. Consulted
+    /// (and cleared) wherever trap entry decides whether the instruction that just completed
+    /// should re-stop the task — that decision lives in `trap.rs`, outside this tree's in-scope
+    /// files.
+    pub single_step: bool,
 }
 
 impl TaskControlBlockInner {
@@ -102,7 +416,7 @@ impl TaskControlBlockInner {
         self.trap_cx_ppn.get_mut()
     }
     pub fn get_user_token(&self) -> usize {
-        self.memory_set.token()
+        self.memory_set.exclusive_access().token()
     }
     fn get_status(&self) -> TaskStatus {
         self.task_status
@@ -111,11 +425,12 @@ impl TaskControlBlockInner {
         self.get_status() == TaskStatus::Zombie
     }
     pub fn alloc_fd(&mut self) -> usize {
-        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
+        let mut fd_table = self.fd_table.exclusive_access();
+        if let Some(fd) = (0..fd_table.len()).find(|fd| fd_table[*fd].is_none()) {
             fd
         } else {
-            self.fd_table.push(None);
-            self.fd_table.len() - 1
+            fd_table.push(None);
+            fd_table.len() - 1
         }
     }
 }
@@ -136,6 +451,9 @@ impl TaskControlBlock {
         let pid_handle = pid_alloc();
         let kernel_stack = kstack_alloc();
         let kernel_stack_top = kernel_stack.get_top();
+        let process_pid = pid_handle.0;
+        let tid_allocator = Arc::new(unsafe { UPSafeCell::new(TidAllocator::new()) });
+        let tid = tid_allocator.exclusive_access().alloc();
         // push a task context which goes to trap_return to the top of kernel stack
         // let task_cx_ptr = kernel_stack.push_on_top(TaskContext::goto_trap_return());
         let task_control_block = Self {
@@ -147,30 +465,47 @@ impl TaskControlBlock {
                     base_size: user_sp,
                     task_cx: TaskContext::goto_trap_return(kernel_stack_top),
                     task_status: TaskStatus::Ready,
-                    memory_set,
+                    memory_set: Arc::new(unsafe { UPSafeCell::new(memory_set) }),
+                    vma_index: Arc::new(unsafe { UPSafeCell::new(VmaIndex::new()) }),
                     parent: None,
                     children: Vec::new(),
                     exit_code: 0,
-                    fd_table: vec![
-                        // 0 -> stdin
-                        Some(Arc::new(Stdin)),
-                        // 1 -> stdout
-                        Some(Arc::new(Stdout)),
-                        // 2 -> stderr
-                        Some(Arc::new(Stdout)),
-                    ],
+                    fd_table: Arc::new(unsafe {
+                        UPSafeCell::new(vec![
+                            // 0 -> stdin
+                            Some(Arc::new(Stdin) as Arc<dyn File + Send + Sync>),
+                            // 1 -> stdout
+                            Some(Arc::new(Stdout)),
+                            // 2 -> stderr
+                            Some(Arc::new(Stdout)),
+                        ])
+                    }),
+                    uid: 0,
+                    gid: 0,
                     heap_bottom: user_sp,
                     program_brk: user_sp,
-                    start_time: 
+                    start_time:
                         get_time_ms(),
                     task_info:
                         TaskInfo::new(),
                     strid: 0,
-                    pass: 0,
+                    pass: BIG_STRIDE / DEFAULT_PRIORITY,
+                    priority: DEFAULT_PRIORITY,
+                    base_priority: DEFAULT_PRIORITY,
+                    utime_us: 0,
+                    stime_us: 0,
+                    blocked_on_holder: None,
+                    process_pid,
+                    tid_allocator,
+                    threads: Arc::new(unsafe { UPSafeCell::new(Vec::new()) }),
+                    res: Some(TaskUserRes { tid, ustack_base: user_sp }),
+                    seccomp_filter: None,
+                    tracer_pid: None,
+                    single_step: false,
 
                 })
             },
-            
+
         };
         // prepare TrapContext in user space
         let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
@@ -195,8 +530,11 @@ impl TaskControlBlock {
 
         // **** access current TCB exclusively
         let mut inner = self.inner_exclusive_access();
-        // substitute memory_set
-        inner.memory_set = memory_set;
+        // substitute memory_set. Any other thread still holding the old `Arc` keeps seeing the
+        // address space being replaced, rather than the new one — acceptable here since exec()
+        // on a multithreaded process is expected to have torn down its other threads first, same
+        // as POSIX execve(2).
+        inner.memory_set = Arc::new(unsafe { UPSafeCell::new(memory_set) });
         // update trap_cx ppn
         inner.trap_cx_ppn = trap_cx_ppn;
         // initialize trap_cx
@@ -211,29 +549,108 @@ impl TaskControlBlock {
         // **** release current PCB
     }
 
-    /// Fork from parent to child
+    /// Fork from parent to child: a `clone_task` call with no flags set, so every resource is
+    /// deep-copied rather than shared — the historical `fork(2)` behavior, kept as its own
+    /// method since `sys_spawn` builds directly on it (fork, then `exec` before the child ever
+    /// runs).
     pub fn fork(self: &Arc<TaskControlBlock>) -> Arc<TaskControlBlock> {
+        self.clone_task(CloneFlags::empty(), 0)
+    }
+
+    /// Generalized `fork`/`sys_clone` backing: build a new task from `self`, sharing whichever
+    /// resources `flags` asks for instead of deep-copying them.
+    ///
+    /// `CLONE_VM` shares `memory_set` (an `Arc::clone`, not a copy) instead of duplicating the
+    /// address space via `MemorySet::from_existed_user` — the new task becomes a thread sharing
+    /// memory with `self`, and the usual `Arc` refcounting means the address space is only torn
+    /// down once every task sharing it has exited, with no special-casing needed in the exit
+    /// path. `CLONE_FILES` likewise shares `fd_table` instead of cloning each open file into a
+    /// fresh table. `CLONE_THREAD` makes the new task's `process_pid`/`tid_allocator`/`threads`
+    /// the same as `self`'s (another thread of the same logical process) instead of allocating
+    /// fresh ones. If `stack != 0`, the new task's user `sp` is set to it, for the case where the
+    /// caller (e.g. a `pthread_create`-style libc) has already allocated a stack for the new
+    /// task rather than wanting one of this kernel's own `TaskUserRes` stacks mapped for it.
+    pub fn clone_task(
+        self: &Arc<TaskControlBlock>,
+        flags: CloneFlags,
+        stack: usize,
+    ) -> Arc<TaskControlBlock> {
         // ---- access parent PCB exclusively
         let mut parent_inner = self.inner_exclusive_access();
-        // copy user space(include trap context)
-        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
-        let trap_cx_ppn = memory_set
-            .translate(VirtAddr::from(TRAP_CONTEXT_BASE).into())
-            .unwrap()
-            .ppn();
+
+        let memory_set = if flags.contains(CloneFlags::CLONE_VM) {
+            Arc::clone(&parent_inner.memory_set)
+        } else {
+            Arc::new(unsafe {
+                UPSafeCell::new(MemorySet::from_existed_user(
+                    &parent_inner.memory_set.exclusive_access(),
+                ))
+            })
+        };
+        let vma_index = if flags.contains(CloneFlags::CLONE_VM) {
+            Arc::clone(&parent_inner.vma_index)
+        } else {
+            Arc::new(unsafe {
+                UPSafeCell::new(parent_inner.vma_index.exclusive_access().clone())
+            })
+        };
         // alloc a pid and a kernel stack in kernel space
         let pid_handle = pid_alloc();
         let kernel_stack = kstack_alloc();
         let kernel_stack_top = kernel_stack.get_top();
-        // copy fd table
-        let mut new_fd_table: Vec<Option<Arc<dyn File + Send + Sync>>> = Vec::new();
-        for fd in parent_inner.fd_table.iter() {
-            if let Some(file) = fd {
-                new_fd_table.push(Some(file.clone()));
-            } else {
-                new_fd_table.push(None);
+
+        let fd_table = if flags.contains(CloneFlags::CLONE_FILES) {
+            Arc::clone(&parent_inner.fd_table)
+        } else {
+            let mut new_fd_table: Vec<Option<Arc<dyn File + Send + Sync>>> = Vec::new();
+            for fd in parent_inner.fd_table.exclusive_access().iter() {
+                new_fd_table.push(fd.clone());
             }
-        }
+            Arc::new(unsafe { UPSafeCell::new(new_fd_table) })
+        };
+
+        let (process_pid, tid_allocator, threads) = if flags.contains(CloneFlags::CLONE_THREAD) {
+            (
+                parent_inner.process_pid,
+                Arc::clone(&parent_inner.tid_allocator),
+                Arc::clone(&parent_inner.threads),
+            )
+        } else {
+            (
+                pid_handle.0,
+                Arc::new(unsafe { UPSafeCell::new(TidAllocator::new()) }),
+                Arc::new(unsafe { UPSafeCell::new(Vec::new()) }),
+            )
+        };
+        let tid = tid_allocator.exclusive_access().alloc();
+
+        // `CLONE_VM` shares `memory_set` with whatever else is already using it, so reusing
+        // TRAP_CONTEXT_BASE here would alias the same physical trap-context frame every other
+        // CLONE_VM'd task translates that address to — the next syscall/timer trap on either
+        // task would clobber the other's saved registers. Map this task its own tid-indexed
+        // trap-context page below TRAP_CONTEXT_BASE instead, the same slot `create_thread` maps
+        // for a new thread. A non-CLONE_VM clone gets its own private `memory_set`
+        // (`MemorySet::from_existed_user` above already copied the single trap-context page that
+        // sits at TRAP_CONTEXT_BASE in it), so translating TRAP_CONTEXT_BASE there is correct.
+        let trap_cx_ppn = if flags.contains(CloneFlags::CLONE_VM) {
+            let region_size = USER_STACK_SIZE + PAGE_SIZE;
+            let ustack_base = TRAP_CONTEXT_BASE - (tid + 1) * region_size;
+            let trap_cx_base = ustack_base + USER_STACK_SIZE;
+            let mut ms = memory_set.exclusive_access();
+            ms.insert_framed_area(
+                VirtAddr(trap_cx_base).into(),
+                VirtAddr(trap_cx_base + PAGE_SIZE).into(),
+                MapPermission::R | MapPermission::W,
+            );
+            ms.translate(VirtAddr(trap_cx_base).into()).unwrap().ppn()
+        } else {
+            memory_set
+                .exclusive_access()
+                .translate(VirtAddr::from(TRAP_CONTEXT_BASE).into())
+                .unwrap()
+                .ppn()
+        };
+
         let task_control_block = Arc::new(TaskControlBlock {
             pid: pid_handle,
             kernel_stack,
@@ -244,18 +661,33 @@ impl TaskControlBlock {
                     task_cx: TaskContext::goto_trap_return(kernel_stack_top),
                     task_status: TaskStatus::Ready,
                     memory_set,
+                    vma_index,
                     parent: Some(Arc::downgrade(self)),
                     children: Vec::new(),
                     exit_code: 0,
-                    fd_table: new_fd_table,
+                    fd_table,
+                    uid: parent_inner.uid,
+                    gid: parent_inner.gid,
                     heap_bottom: parent_inner.heap_bottom,
                     program_brk: parent_inner.program_brk,
-                    start_time: 
+                    start_time:
                         get_time_ms(),
                     task_info:
                         TaskInfo::new(),
                     strid: 0,
-                    pass: 0,
+                    pass: parent_inner.pass,
+                    priority: parent_inner.priority,
+                    base_priority: parent_inner.base_priority,
+                    utime_us: 0,
+                    stime_us: 0,
+                    blocked_on_holder: None,
+                    process_pid,
+                    tid_allocator,
+                    threads,
+                    res: Some(TaskUserRes { tid, ustack_base: parent_inner.base_size }),
+                    seccomp_filter: parent_inner.seccomp_filter.clone(),
+                    tracer_pid: None,
+                    single_step: false,
                 })
             },
         });
@@ -265,6 +697,9 @@ impl TaskControlBlock {
         // **** access child PCB exclusively
         let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
         trap_cx.kernel_sp = kernel_stack_top;
+        if stack != 0 {
+            trap_cx.x[2] = stack;
+        }
         // return
         task_control_block
         // **** release child PCB
@@ -286,14 +721,13 @@ impl TaskControlBlock {
         if new_brk < heap_bottom as isize {
             return None;
         }
-        let result = if size < 0 {
-            inner
-                .memory_set
-                .shrink_to(VirtAddr(heap_bottom), VirtAddr(new_brk as usize))
-        } else {
-            inner
-                .memory_set
-                .append_to(VirtAddr(heap_bottom), VirtAddr(new_brk as usize))
+        let result = {
+            let mut memory_set = inner.memory_set.exclusive_access();
+            if size < 0 {
+                memory_set.shrink_to(VirtAddr(heap_bottom), VirtAddr(new_brk as usize))
+            } else {
+                memory_set.append_to(VirtAddr(heap_bottom), VirtAddr(new_brk as usize))
+            }
         };
         if result {
             inner.program_brk = new_brk as usize;
@@ -303,11 +737,11 @@ impl TaskControlBlock {
         }
     }
 
-    
+
 }
 
 #[derive(Copy, Clone, PartialEq)]
-/// task status: UnInit, Ready, Running, Exited
+/// task status: UnInit, Ready, Running, Stopped, Zombie
 pub enum TaskStatus {
     /// uninitialized
     UnInit,
@@ -315,6 +749,10 @@ pub enum TaskStatus {
     Ready,
     /// running
     Running,
+    /// stopped under ptrace (a single-step or trap stop), waiting on `PTRACE_CONT`/
+    /// `PTRACE_SINGLESTEP` to resume; not runnable, but distinct from `Zombie` since the task
+    /// hasn't exited and its tracer reports the stop via `sys_waitpid` without reaping it
+    Stopped,
     /// exited
     Zombie,
 }
@@ -356,4 +794,4 @@ impl TaskInfo{
     pub fn add_syscall_times(&mut self, syscall_id: usize){
         self.syscall_times[syscall_id] += 1;
     }
-}
\ No newline at end of file
+}